@@ -0,0 +1,229 @@
+use chrono::{DateTime, Duration, Utc};
+use futures::stream::StreamExt;
+use mongodb::{bson::{doc, oid::ObjectId, serde_helpers::chrono_datetime_as_bson_datetime, to_bson, Bson}, options::{FindOneOptions, FindOptions}, Collection};
+use serde::{Deserialize, Serialize};
+
+use crate::Backend;
+use crate::database::evidence::{Evidence, EvidenceTarget, EvidenceVisibility};
+use crate::database::moderation::{BanDuration, BanEntry};
+use crate::database::optional_bson_datetime;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ModerationActionKind {
+    Warning,
+    Kick,
+    Mute,
+    UploadRestriction,
+    Ban
+}
+
+impl ModerationActionKind {
+    // Warnings and kicks are only kept on record, the rest stop the player from doing something while active
+    pub fn is_restriction(&self) -> bool {
+        matches!(self, ModerationActionKind::Mute | ModerationActionKind::UploadRestriction | ModerationActionKind::Ban)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ModerationAction {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    #[serde(rename = "userId")]
+    pub user_id: i64,
+    pub kind: ModerationActionKind,
+    pub moderator: String,
+    pub reason: String,
+    #[serde(rename = "createdTime", with = "chrono_datetime_as_bson_datetime")]
+    pub created_time: DateTime<Utc>,
+    // None never expires
+    #[serde(rename = "expiresTime", with = "optional_bson_datetime")]
+    pub expires_time: Option<DateTime<Utc>>,
+    pub revoked: bool,
+    // The counted kind of the escalation rule that took this action, None for actions taken by moderators
+    #[serde(rename = "escalatedFrom", default)]
+    pub escalated_from: Option<ModerationActionKind>
+}
+
+impl ModerationAction {
+    pub fn is_active(&self, time_now: DateTime<Utc>) -> bool {
+        !self.revoked && self.expires_time.map(|expires_time| expires_time > time_now).unwrap_or(true)
+    }
+}
+
+// e.g. 3 warnings within 30 days become a permanent ban
+#[derive(Debug, Clone)]
+pub struct EscalationRule {
+    pub counted_kind: ModerationActionKind,
+    pub count: u32,
+    pub window: Duration,
+    pub escalated_kind: ModerationActionKind,
+    // None is permanent
+    pub escalated_duration: Option<Duration>
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ActiveRestrictions {
+    // Bans are only ever tracked through `bannedplayers`, so unbans, appeals and shortened bans apply right away
+    pub ban: Option<BanEntry>,
+    // Bans of linked accounts don't restrict the user by themselves, they're for moderators to follow up on
+    #[serde(rename = "linkedBans")]
+    pub linked_bans: Vec<BanEntry>,
+    // Ban actions are left out, they're only kept for the history
+    pub actions: Vec<ModerationAction>
+}
+
+impl ActiveRestrictions {
+    pub fn is_banned(&self) -> bool {
+        self.ban.is_some()
+    }
+
+    pub fn is_muted(&self) -> bool {
+        self.has_active(ModerationActionKind::Mute)
+    }
+
+    pub fn can_upload_maps(&self) -> bool {
+        !self.is_banned() && !self.has_active(ModerationActionKind::UploadRestriction)
+    }
+
+    fn has_active(&self, kind: ModerationActionKind) -> bool {
+        self.actions.iter().any(|action| action.kind == kind)
+    }
+}
+
+fn new_moderation_action(user_id: u64, kind: ModerationActionKind, moderator: &str, reason: &str) -> ModerationAction {
+    ModerationAction {
+        id: None,
+        user_id: user_id as i64,
+        kind,
+        moderator: moderator.to_string(),
+        reason: reason.to_string(),
+        created_time: Utc::now(),
+        expires_time: None,
+        revoked: false,
+        escalated_from: None
+    }
+}
+
+fn bson_kind(kind: ModerationActionKind) -> Result<Bson, Box<dyn std::error::Error>> {
+    Ok(to_bson(&kind)?)
+}
+
+impl Backend {
+    fn moderation_actions_collection(&self) -> Collection<ModerationAction> {
+        self.get_database().collection("moderationactions")
+    }
+
+    pub(crate) async fn find_moderation_action(&self, action_id: ObjectId) -> Result<Option<ModerationAction>, Box<dyn std::error::Error>> {
+        Ok(self.moderation_actions_collection().find_one(doc! { "_id": action_id }, None).await?)
+    }
+
+    pub fn set_escalation_rules(&mut self, rules: Vec<EscalationRule>) {
+        self.escalation_rules = rules;
+    }
+
+    // Returns the recorded action followed by any actions it escalated into.
+    // The evidence is attached to the recorded action, see `list_evidence`.
+    pub async fn take_moderation_action(&self, user_id: u64, kind: ModerationActionKind, duration: Option<Duration>, moderator: &str, reason: &str, evidence: Vec<(Evidence, EvidenceVisibility)>) -> Result<Vec<ModerationAction>, Box<dyn std::error::Error>> {
+        let action = self.record_moderation_action(new_moderation_action(user_id, kind, moderator, reason), duration).await?;
+        let action_id = action.id.ok_or("Moderation action was not given an ID.")?;
+        for (evidence, visibility) in evidence {
+            self.add_evidence(EvidenceTarget::Action { action_id }, evidence, visibility, moderator).await?;
+        }
+        let mut taken = vec![action];
+
+        let time_now = Utc::now();
+        for rule in self.escalation_rules.iter().filter(|rule| rule.counted_kind == kind) {
+            // Actions already counted towards an escalation of this rule don't count again
+            let window_start = time_now.checked_sub_signed(rule.window).unwrap_or(DateTime::<Utc>::MIN_UTC);
+            let created_time = match self.find_last_escalation(user_id, rule).await? {
+                Some(last_escalation) if last_escalation.created_time >= window_start => doc! { "$gt": last_escalation.created_time },
+                _ => doc! { "$gte": window_start }
+            };
+
+            let counted = self.moderation_actions_collection().count_documents(doc! {
+                "userId": user_id as i64,
+                "kind": bson_kind(rule.counted_kind)?,
+                "revoked": false,
+                "createdTime": created_time
+            }, None).await?;
+
+            if counted >= rule.count as u64 {
+                let reason = format!("Automatic escalation: {} {:?} actions within {} days.", counted, rule.counted_kind, rule.window.num_days());
+                let mut escalation = new_moderation_action(user_id, rule.escalated_kind, "Automatic escalation", &reason);
+                escalation.escalated_from = Some(rule.counted_kind);
+                taken.push(self.record_moderation_action(escalation, rule.escalated_duration).await?);
+            }
+        }
+
+        Ok(taken)
+    }
+
+    async fn find_last_escalation(&self, user_id: u64, rule: &EscalationRule) -> Result<Option<ModerationAction>, Box<dyn std::error::Error>> {
+        let options = FindOneOptions::builder().sort(doc! { "createdTime": -1 }).build();
+        Ok(self.moderation_actions_collection().find_one(doc! {
+            "userId": user_id as i64,
+            "kind": bson_kind(rule.escalated_kind)?,
+            "escalatedFrom": bson_kind(rule.counted_kind)?
+        }, options).await?)
+    }
+
+    async fn record_moderation_action(&self, mut action: ModerationAction, duration: Option<Duration>) -> Result<ModerationAction, Box<dyn std::error::Error>> {
+        let duration = duration.map(BanDuration::For).unwrap_or(BanDuration::Permanent);
+        duration.validate()?;
+        let user_id = action.user_id as u64;
+
+        // Bans are still enforced through `bannedplayers`, the action is kept for the history.
+        // Escalations never replace a longer ban a moderator already gave.
+        if action.kind == ModerationActionKind::Ban {
+            match action.escalated_from {
+                Some(_) => { self.extend_ban_entry(user_id, duration, &action.moderator, &action.reason).await?; },
+                None => { self.ban_player(user_id, duration, &action.moderator, &action.reason, false).await?; }
+            }
+        }
+
+        action.expires_time = duration.ends_at(action.created_time);
+        let result = self.moderation_actions_collection().insert_one(&action, None).await?;
+        action.id = result.inserted_id.as_object_id();
+
+        Ok(action)
+    }
+
+    // Revoking a ban action doesn't unban the player, `unban_player` does
+    pub async fn revoke_moderation_action(&self, action_id: ObjectId) -> Result<(), Box<dyn std::error::Error>> {
+        let update = doc! { "$set": doc! {
+            "revoked": true
+        } };
+
+        let result = self.moderation_actions_collection().update_one(doc! { "_id": action_id }, update, None).await?;
+        if result.matched_count == 0 {
+            return Err("Moderation action does not exist.".into())
+        }
+
+        Ok(())
+    }
+
+    // Newest first
+    pub async fn get_moderation_history(&self, user_id: u64) -> Result<Vec<ModerationAction>, Box<dyn std::error::Error>> {
+        let options = FindOptions::builder().sort(doc! { "createdTime": -1 }).build();
+        let mut cursor = self.moderation_actions_collection().find(doc! { "userId": user_id as i64 }, options).await?;
+
+        let mut result: Vec<ModerationAction> = Vec::new();
+        while let Some(action) = cursor.next().await {
+            result.push(action?);
+        }
+
+        Ok(result)
+    }
+
+    pub async fn get_active_restrictions(&self, user_id: u64) -> Result<ActiveRestrictions, Box<dyn std::error::Error>> {
+        let time_now = Utc::now();
+
+        let ban_check = self.check_ban(user_id).await?;
+        let actions = self.get_moderation_history(user_id).await?
+            .into_iter()
+            .filter(|action| action.kind.is_restriction() && action.kind != ModerationActionKind::Ban && action.is_active(time_now))
+            .collect();
+
+        Ok(ActiveRestrictions { ban: ban_check.ban, linked_bans: ban_check.linked_bans, actions })
+    }
+}
@@ -1,4 +1,5 @@
-use mongodb::{bson::doc, Collection};
+use chrono::{DateTime, Utc};
+use mongodb::{bson::{doc, serde_helpers::chrono_datetime_as_bson_datetime}, Collection};
 use serde::{ Deserialize, Serialize };
 
 use crate::Backend;
@@ -13,8 +14,8 @@ pub struct ApiKey {
     #[serde(rename = "associatedDiscordUser")]
     pub associated_discord_user: Option<String>,
     pub enabled: bool,
-    #[serde(rename = "timeCreated")]
-    pub time_created: f64,
+    #[serde(rename = "timeCreated", with = "chrono_datetime_as_bson_datetime")]
+    pub time_created: DateTime<Utc>,
 }
 
 impl Backend {
@@ -23,13 +24,10 @@ impl Backend {
         let api_keys_collection: Collection<ApiKey> = database.collection("apikeys");
         
         // my reaction when rust
-        let api_key_generator: IDConverter = IDConverter::new(
-            &"qwertyuiopasdfghjklzxcvbnm0192837465".to_string(),
-            &"5432189076".to_string()
-        );
+        let api_key_generator: IDConverter = IDConverter::new("qwertyuiopasdfghjklzxcvbnm0192837465")?;
 
         let doc_count: u64 = api_keys_collection.count_documents(None, None).await?;
-        api_key_generator.to_short(doc_count * 8 + datetime_now() * 2)
+        Ok(api_key_generator.to_short(doc_count * 8 + datetime_now() * 2)?)
     }
 
     pub async fn find_api_key_entry(&self, api_key: &str) -> Result<Option<ApiKey>, Box<dyn std::error::Error>> {
@@ -67,7 +65,7 @@ impl Backend {
             assign_owner: "None".to_string(),
             associated_discord_user: None,
             enabled: true,
-            time_created: datetime_now() as f64
+            time_created: Utc::now()
         };
         api_keys_collection.insert_one(doc, None).await?;
         Ok(())
@@ -103,10 +101,30 @@ impl Backend {
         Ok(result)
     }
 
+    // e.g. for keys disabled by a permanent ban that was lifted, see `ban_player`
+    pub async fn set_api_key_enabled(&self, api_key: &str, enabled: bool) -> Result<(), Box<dyn std::error::Error>> {
+        let database = self.get_database();
+
+        let api_keys_collection: Collection<ApiKey> = database.collection("apikeys");
+
+        let result = api_keys_collection.update_one(
+            doc! {
+                "value": api_key.to_string()
+            },
+            doc! { "$set": { "enabled": enabled } },
+            None
+        ).await?;
+
+        if result.matched_count == 0 {
+            return Err("API key does not exist.".into())
+        }
+        Ok(())
+    }
+
     pub async fn is_valid_api_key(&self, api_key: &str) -> Result<bool, Box<dyn std::error::Error>> {
         let api_key_entry = self.find_api_key_entry(api_key).await?;
         match api_key_entry {
-            Some(entry) => Ok(entry.enabled == true),
+            Some(entry) => Ok(entry.enabled),
             None => Ok(false)
         }
     }
@@ -0,0 +1,247 @@
+use std::time::Duration;
+use chrono::{DateTime, Utc};
+use futures::stream::StreamExt;
+use mongodb::{bson::{doc, oid::ObjectId, serde_helpers::chrono_datetime_as_bson_datetime, to_bson, Document}, options::FindOptions, Collection};
+use serde::{Deserialize, Serialize};
+
+use crate::Backend;
+use crate::database::optional_bson_datetime;
+
+pub(crate) const DEFAULT_APPEAL_COOLDOWN: Duration = Duration::from_secs(14 * 24 * 60 * 60);
+const NOT_RESOLVABLE_ERROR: &str = "Appeal does not exist, is already resolved or is claimed by another moderator.";
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AppealState {
+    Pending,
+    Claimed,
+    Accepted,
+    Rejected
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AppealEventKind {
+    Submitted,
+    Claimed,
+    Commented,
+    Accepted,
+    Rejected
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AppealEvent {
+    pub kind: AppealEventKind,
+    // The appealing player's user ID for submissions, the moderator otherwise
+    pub actor: String,
+    pub text: Option<String>,
+    #[serde(with = "chrono_datetime_as_bson_datetime")]
+    pub time: DateTime<Utc>
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Appeal {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    #[serde(rename = "userId")]
+    pub user_id: i64,
+    pub text: String,
+    pub state: AppealState,
+    #[serde(rename = "claimedBy")]
+    pub claimed_by: Option<String>,
+    // bannedTime of the ban being appealed
+    #[serde(rename = "banTime", with = "chrono_datetime_as_bson_datetime")]
+    pub ban_time: DateTime<Utc>,
+    #[serde(rename = "submittedTime", with = "chrono_datetime_as_bson_datetime")]
+    pub submitted_time: DateTime<Utc>,
+    #[serde(rename = "resolvedTime", with = "optional_bson_datetime")]
+    pub resolved_time: Option<DateTime<Utc>>,
+    pub history: Vec<AppealEvent>
+}
+
+fn appeal_event(kind: AppealEventKind, actor: &str, text: Option<&str>) -> Result<Document, Box<dyn std::error::Error>> {
+    let event = AppealEvent {
+        kind,
+        actor: actor.to_string(),
+        text: text.map(|text| text.to_string()),
+        time: Utc::now()
+    };
+
+    match to_bson(&event)? {
+        mongodb::bson::Bson::Document(document) => Ok(document),
+        _ => Err("Appeal event could not be serialized.".into())
+    }
+}
+
+// Open appeals can be resolved by anyone until they're claimed, then only by whoever claimed them
+fn resolvable_by(appeal_id: ObjectId, moderator: &str) -> Result<Document, Box<dyn std::error::Error>> {
+    Ok(doc! {
+        "_id": appeal_id,
+        "$or": [
+            { "state": to_bson(&AppealState::Pending)? },
+            { "state": to_bson(&AppealState::Claimed)?, "claimedBy": moderator }
+        ]
+    })
+}
+
+impl Backend {
+    fn appeals_collection(&self) -> Collection<Appeal> {
+        self.get_database().collection("banappeals")
+    }
+
+    pub fn set_appeal_cooldown(&mut self, cooldown: Duration) {
+        self.appeal_cooldown = cooldown;
+    }
+
+    pub async fn submit_appeal(&self, user_id: u64, text: &str) -> Result<Appeal, Box<dyn std::error::Error>> {
+        let ban = match self.find_ban_entry(user_id).await?.filter(|ban| ban.is_active(Utc::now())) {
+            Some(ban) => ban,
+            None => return Err("User is not banned.".into())
+        };
+
+        let options = FindOptions::builder().sort(doc! { "submittedTime": -1 }).limit(1).build();
+        let mut cursor = self.appeals_collection().find(doc! { "userId": user_id as i64 }, options).await?;
+        if let Some(last_appeal) = cursor.next().await {
+            let last_appeal = last_appeal?;
+            if matches!(last_appeal.state, AppealState::Pending | AppealState::Claimed) {
+                return Err("User already has an open appeal.".into())
+            }
+
+            // A new ban can be appealed right away
+            let time_now = Utc::now();
+            let cooldown = chrono::Duration::from_std(self.appeal_cooldown).unwrap_or(chrono::Duration::max_value());
+            let cooldown_over = last_appeal.submitted_time.checked_add_signed(cooldown).unwrap_or(DateTime::<Utc>::MAX_UTC);
+            if last_appeal.ban_time == ban.banned_time && cooldown_over > time_now {
+                return Err(format!("User can appeal again in {} minutes.", (cooldown_over - time_now).num_minutes() + 1).into())
+            }
+        }
+
+        let time_now = Utc::now();
+        let mut appeal = Appeal {
+            id: None,
+            user_id: user_id as i64,
+            text: text.to_string(),
+            state: AppealState::Pending,
+            claimed_by: None,
+            ban_time: ban.banned_time,
+            submitted_time: time_now,
+            resolved_time: None,
+            history: vec![AppealEvent {
+                kind: AppealEventKind::Submitted,
+                actor: user_id.to_string(),
+                text: Some(text.to_string()),
+                time: time_now
+            }]
+        };
+        let result = self.appeals_collection().insert_one(&appeal, None).await?;
+        appeal.id = result.inserted_id.as_object_id();
+
+        Ok(appeal)
+    }
+
+    pub async fn get_appeal(&self, appeal_id: ObjectId) -> Result<Option<Appeal>, Box<dyn std::error::Error>> {
+        Ok(self.appeals_collection().find_one(doc! { "_id": appeal_id }, None).await?)
+    }
+
+    // Pending and claimed appeals, oldest first
+    pub async fn get_open_appeals(&self) -> Result<Vec<Appeal>, Box<dyn std::error::Error>> {
+        let filter = doc! { "state": { "$in": [to_bson(&AppealState::Pending)?, to_bson(&AppealState::Claimed)?] } };
+        let options = FindOptions::builder().sort(doc! { "submittedTime": 1 }).build();
+        let mut cursor = self.appeals_collection().find(filter, options).await?;
+
+        let mut result: Vec<Appeal> = Vec::new();
+        while let Some(appeal) = cursor.next().await {
+            result.push(appeal?);
+        }
+
+        Ok(result)
+    }
+
+    pub async fn get_user_appeals(&self, user_id: u64) -> Result<Vec<Appeal>, Box<dyn std::error::Error>> {
+        let options = FindOptions::builder().sort(doc! { "submittedTime": -1 }).build();
+        let mut cursor = self.appeals_collection().find(doc! { "userId": user_id as i64 }, options).await?;
+
+        let mut result: Vec<Appeal> = Vec::new();
+        while let Some(appeal) = cursor.next().await {
+            result.push(appeal?);
+        }
+
+        Ok(result)
+    }
+
+    pub async fn claim_appeal(&self, appeal_id: ObjectId, moderator: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let update = doc! {
+            "$set": { "state": to_bson(&AppealState::Claimed)?, "claimedBy": moderator },
+            "$push": { "history": appeal_event(AppealEventKind::Claimed, moderator, None)? }
+        };
+
+        let filter = doc! { "_id": appeal_id, "state": to_bson(&AppealState::Pending)? };
+        if self.appeals_collection().update_one(filter, update, None).await?.matched_count == 0 {
+            return Err("Appeal does not exist or is not pending.".into())
+        }
+
+        Ok(())
+    }
+
+    pub async fn comment_on_appeal(&self, appeal_id: ObjectId, author: &str, text: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let update = doc! {
+            "$push": { "history": appeal_event(AppealEventKind::Commented, author, Some(text))? }
+        };
+
+        if self.appeals_collection().update_one(doc! { "_id": appeal_id }, update, None).await?.matched_count == 0 {
+            return Err("Appeal does not exist.".into())
+        }
+
+        Ok(())
+    }
+
+    // `remaining` None lifts the ban, otherwise the ban ends after it (it's never extended).
+    // Fails when the user has been banned again since appealing, a newer ban needs an appeal of its own.
+    pub async fn accept_appeal(&self, appeal_id: ObjectId, moderator: &str, comment: Option<&str>, remaining: Option<chrono::Duration>) -> Result<(), Box<dyn std::error::Error>> {
+        let appeal = match self.get_appeal(appeal_id).await? {
+            Some(appeal) => appeal,
+            None => return Err("Appeal does not exist.".into())
+        };
+
+        let user_id = appeal.user_id as u64;
+        match self.find_ban_entry(user_id).await? {
+            Some(ban) if ban.banned_time == appeal.ban_time => {},
+            _ => return Err("The appealed ban is no longer in place, reject the appeal instead.".into())
+        }
+
+        // The ban is changed before the appeal is marked accepted, so checking that it can be resolved comes first
+        if self.appeals_collection().count_documents(resolvable_by(appeal_id, moderator)?, None).await? == 0 {
+            return Err(NOT_RESOLVABLE_ERROR.into())
+        }
+
+        match remaining {
+            None => self.unban_player(user_id).await?,
+            Some(remaining) => match Utc::now().checked_add_signed(remaining) {
+                Some(banned_until) => self.shorten_ban(user_id, banned_until).await?,
+                None => return Err("Remaining ban duration is too long.".into())
+            }
+        }
+
+        // Only left to a moderator claiming the appeal in between, the ban change isn't undone
+        if let Err(error) = self.resolve_appeal(appeal_id, moderator, comment, AppealState::Accepted, AppealEventKind::Accepted).await {
+            return Err(format!("The ban was changed, but the appeal could not be marked as accepted: {}", error).into())
+        }
+
+        Ok(())
+    }
+
+    pub async fn reject_appeal(&self, appeal_id: ObjectId, moderator: &str, comment: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+        self.resolve_appeal(appeal_id, moderator, comment, AppealState::Rejected, AppealEventKind::Rejected).await
+    }
+
+    async fn resolve_appeal(&self, appeal_id: ObjectId, moderator: &str, comment: Option<&str>, state: AppealState, event: AppealEventKind) -> Result<(), Box<dyn std::error::Error>> {
+        let update = doc! {
+            "$set": { "state": to_bson(&state)?, "claimedBy": moderator, "resolvedTime": Utc::now() },
+            "$push": { "history": appeal_event(event, moderator, comment)? }
+        };
+
+        if self.appeals_collection().update_one(resolvable_by(appeal_id, moderator)?, update, None).await?.matched_count == 0 {
+            return Err(NOT_RESOLVABLE_ERROR.into())
+        }
+
+        Ok(())
+    }
+}
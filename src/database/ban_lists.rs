@@ -0,0 +1,307 @@
+use std::collections::HashMap;
+use chrono::{DateTime, Utc};
+use mongodb::{bson::doc, Collection};
+use serde::{Deserialize, Serialize};
+
+use crate::Backend;
+use crate::database::moderation::{self, BanEntry};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BanListFormat {
+    // With a header row named after the BanEntry fields (userId, bannedTime, bannedUntil, moderator, reason)
+    Csv,
+    // An array of BanEntry objects
+    Json
+}
+
+// BanEntry as it appears in ban lists, times are RFC 3339 and an empty bannedUntil is permanent
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct BanListRow {
+    #[serde(rename = "userId")]
+    user_id: i64,
+    #[serde(rename = "bannedTime")]
+    banned_time: DateTime<Utc>,
+    #[serde(rename = "bannedUntil")]
+    banned_until: Option<DateTime<Utc>>,
+    moderator: String,
+    reason: String
+}
+
+impl From<BanListRow> for BanEntry {
+    fn from(row: BanListRow) -> Self {
+        BanEntry {
+            user_id: row.user_id,
+            banned_time: row.banned_time,
+            banned_until: row.banned_until,
+            moderator: row.moderator,
+            reason: row.reason
+        }
+    }
+}
+
+impl From<BanEntry> for BanListRow {
+    fn from(ban: BanEntry) -> Self {
+        BanListRow {
+            user_id: ban.user_id,
+            banned_time: ban.banned_time,
+            banned_until: ban.banned_until,
+            moderator: ban.moderator,
+            reason: ban.reason
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BanConflictPolicy {
+    Skip,
+    Overwrite,
+    // Permanent bans are the longest
+    KeepLongest
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BanExportScope {
+    Active,
+    // Expired bans still on record as well
+    All
+}
+
+#[derive(Debug, Clone)]
+pub struct BanImportOptions {
+    pub format: BanListFormat,
+    pub conflict_policy: BanConflictPolicy,
+    // Validates and reports what would happen without writing anything
+    pub dry_run: bool
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BanImportRowError {
+    // 1-based, not counting the CSV header
+    pub row: usize,
+    pub message: String
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct BanImportReport {
+    #[serde(rename = "dryRun")]
+    pub dry_run: bool,
+    pub inserted: usize,
+    pub overwritten: usize,
+    pub skipped: usize,
+    pub errors: Vec<BanImportRowError>
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct BanImportProgress {
+    pub processed: usize,
+    pub total: usize
+}
+
+enum ImportDecision {
+    Insert,
+    Overwrite,
+    Skip
+}
+
+fn parse_ban_list(data: &[u8], format: BanListFormat) -> Result<Vec<Result<BanEntry, String>>, Box<dyn std::error::Error>> {
+    match format {
+        BanListFormat::Csv => {
+            let mut reader = csv::Reader::from_reader(data);
+            Ok(reader
+                .deserialize::<BanListRow>()
+                .map(|row| row.map(BanEntry::from).map_err(|error| error.to_string()))
+                .collect())
+        },
+        BanListFormat::Json => {
+            // Rows are parsed one by one, so one bad row doesn't fail the whole list
+            let rows: Vec<serde_json::Value> = serde_json::from_slice(data)?;
+            Ok(rows
+                .into_iter()
+                .map(|row| serde_json::from_value::<BanListRow>(row).map(BanEntry::from).map_err(|error| error.to_string()))
+                .collect())
+        }
+    }
+}
+
+fn validate_ban_entry(ban: &BanEntry) -> Result<(), String> {
+    if ban.user_id <= 0 {
+        return Err(format!("userId {} is not a valid Roblox user ID", ban.user_id))
+    }
+    if ban.banned_time.timestamp_millis() <= 0 {
+        return Err("bannedTime must be after 1970".to_string())
+    }
+    if ban.banned_until.is_some_and(|banned_until| banned_until <= ban.banned_time) {
+        return Err("bannedUntil must be empty (permanent) or after bannedTime".to_string())
+    }
+    if ban.moderator.trim().is_empty() {
+        return Err("moderator is empty".to_string())
+    }
+
+    Ok(())
+}
+
+fn is_longer_ban(ban: &BanEntry, than: &BanEntry) -> bool {
+    moderation::is_longer_ban(ban.banned_until, than.banned_until)
+}
+
+impl Backend {
+    // Row errors are collected into the report, only unreadable lists and database failures error out
+    pub async fn import_bans(&self, data: &[u8], options: &BanImportOptions, mut on_progress: impl FnMut(BanImportProgress)) -> Result<BanImportReport, Box<dyn std::error::Error>> {
+        let collection: Collection<BanEntry> = self.get_database().collection("bannedplayers");
+
+        let rows = parse_ban_list(data, options.format)?;
+        let total = rows.len();
+        let mut report = BanImportReport { dry_run: options.dry_run, ..BanImportReport::default() };
+        // Rows already imported from this list, so duplicates are handled the same with and without dry runs
+        let mut imported: HashMap<i64, BanEntry> = HashMap::new();
+
+        for (index, row) in rows.into_iter().enumerate() {
+            let row_number = index + 1;
+            let ban = match row.and_then(|ban| validate_ban_entry(&ban).map(|_| ban)) {
+                Ok(ban) => ban,
+                Err(message) => {
+                    report.errors.push(BanImportRowError { row: row_number, message });
+                    on_progress(BanImportProgress { processed: row_number, total });
+                    continue
+                }
+            };
+
+            let existing = match imported.get(&ban.user_id) {
+                Some(existing) => Some(existing.clone()),
+                None => self.find_ban_entry(ban.user_id as u64).await?
+            };
+            let decision = match (&existing, options.conflict_policy) {
+                (None, _) => ImportDecision::Insert,
+                (Some(_), BanConflictPolicy::Skip) => ImportDecision::Skip,
+                (Some(_), BanConflictPolicy::Overwrite) => ImportDecision::Overwrite,
+                (Some(existing), BanConflictPolicy::KeepLongest) => {
+                    if is_longer_ban(&ban, existing) { ImportDecision::Overwrite } else { ImportDecision::Skip }
+                }
+            };
+
+            match decision {
+                ImportDecision::Skip => report.skipped += 1,
+                ImportDecision::Insert | ImportDecision::Overwrite => {
+                    if !options.dry_run {
+                        let write_result = match decision {
+                            ImportDecision::Insert => collection.insert_one(&ban, None).await.map(|_| ()),
+                            _ => collection.replace_one(doc! { "userId": ban.user_id }, &ban, None).await.map(|_| ())
+                        };
+                        if let Err(error) = write_result {
+                            report.errors.push(BanImportRowError { row: row_number, message: error.to_string() });
+                            on_progress(BanImportProgress { processed: row_number, total });
+                            continue
+                        }
+                    }
+
+                    match decision {
+                        ImportDecision::Insert => report.inserted += 1,
+                        _ => report.overwritten += 1
+                    }
+                    imported.insert(ban.user_id, ban);
+                }
+            }
+
+            on_progress(BanImportProgress { processed: row_number, total });
+        }
+
+        Ok(report)
+    }
+
+    pub async fn export_bans(&self, format: BanListFormat, scope: BanExportScope) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let time_now = Utc::now();
+        let bans: Vec<BanListRow> = self.get_ban_collection().await?
+            .into_iter()
+            .filter(|ban| scope == BanExportScope::All || ban.is_active(time_now))
+            .map(BanListRow::from)
+            .collect();
+
+        match format {
+            BanListFormat::Csv => {
+                let mut writer = csv::Writer::from_writer(Vec::new());
+                for ban in &bans {
+                    writer.serialize(ban)?;
+                }
+                Ok(writer.into_inner().map_err(|error| error.to_string())?)
+            },
+            BanListFormat::Json => Ok(serde_json::to_vec_pretty(&bans)?)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, Duration, Utc};
+    use crate::database::moderation::BanEntry;
+    use super::{is_longer_ban, parse_ban_list, validate_ban_entry, BanListFormat};
+
+    fn time(rfc3339: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(rfc3339).unwrap().with_timezone(&Utc)
+    }
+
+    fn ban(banned_until: Option<DateTime<Utc>>) -> BanEntry {
+        BanEntry {
+            user_id: 1,
+            banned_time: time("2024-01-01T00:00:00Z"),
+            banned_until,
+            moderator: "moderator".to_string(),
+            reason: "reason".to_string()
+        }
+    }
+
+    #[test]
+    fn csv_rows_parse_with_empty_ban_ends_as_permanent() {
+        let csv = "userId,bannedTime,bannedUntil,moderator,reason\n\
+            1,2024-01-01T00:00:00Z,2024-01-02T00:00:00Z,mod,spam\n\
+            2,2024-01-01T00:00:00Z,,mod,exploiting\n\
+            three,2024-01-01T00:00:00Z,,mod,bad row\n";
+
+        let rows = parse_ban_list(csv.as_bytes(), BanListFormat::Csv).unwrap();
+        assert_eq!(rows.len(), 3);
+
+        let first = rows[0].as_ref().unwrap();
+        assert_eq!((first.user_id, first.banned_until), (1, Some(time("2024-01-02T00:00:00Z"))));
+        let second = rows[1].as_ref().unwrap();
+        assert_eq!((second.user_id, second.banned_until, second.reason.as_str()), (2, None, "exploiting"));
+        assert!(rows[2].is_err());
+    }
+
+    #[test]
+    fn json_rows_are_parsed_one_by_one() {
+        let json = r#"[
+            { "userId": 1, "bannedTime": "2024-01-01T00:00:00Z", "bannedUntil": null, "moderator": "mod", "reason": "spam" },
+            { "userId": 2, "bannedTime": 1704067200000, "bannedUntil": null, "moderator": "mod", "reason": "old format" }
+        ]"#;
+
+        let rows = parse_ban_list(json.as_bytes(), BanListFormat::Json).unwrap();
+        assert_eq!(rows[0].as_ref().unwrap().banned_until, None);
+        assert!(rows[1].is_err());
+
+        assert!(parse_ban_list(b"{ \"not\": \"a list\" }", BanListFormat::Json).is_err());
+    }
+
+    #[test]
+    fn invalid_bans_are_rejected() {
+        assert!(validate_ban_entry(&ban(None)).is_ok());
+        assert!(validate_ban_entry(&ban(Some(time("2024-01-02T00:00:00Z")))).is_ok());
+
+        assert!(validate_ban_entry(&BanEntry { user_id: 0, ..ban(None) }).is_err());
+        assert!(validate_ban_entry(&BanEntry { banned_time: time("1970-01-01T00:00:00Z"), ..ban(None) }).is_err());
+        assert!(validate_ban_entry(&ban(Some(time("2024-01-01T00:00:00Z")))).is_err());
+        assert!(validate_ban_entry(&BanEntry { moderator: " ".to_string(), ..ban(None) }).is_err());
+    }
+
+    #[test]
+    fn permanent_bans_are_the_longest() {
+        let day = ban(Some(time("2024-01-02T00:00:00Z")));
+        let week = ban(Some(time("2024-01-02T00:00:00Z") + Duration::days(6)));
+        let permanent = ban(None);
+
+        assert!(is_longer_ban(&week, &day));
+        assert!(!is_longer_ban(&day, &week));
+        assert!(!is_longer_ban(&day, &day));
+        assert!(is_longer_ban(&permanent, &week));
+        assert!(!is_longer_ban(&week, &permanent));
+        assert!(!is_longer_ban(&permanent, &permanent));
+    }
+}
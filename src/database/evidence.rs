@@ -0,0 +1,213 @@
+use chrono::{DateTime, Utc};
+use futures::io::{AsyncReadExt, Cursor};
+use futures::stream::StreamExt;
+use mongodb::{bson::{doc, oid::ObjectId, serde_helpers::chrono_datetime_as_bson_datetime, to_bson, to_vec}, gridfs::GridFsBucket, options::{FindOptions, GridFsBucketOptions}, Collection};
+use serde::{Deserialize, Serialize};
+
+use crate::Backend;
+use crate::roblox::SanitizeChange;
+use crate::shareable_ids::ReplayId;
+
+// Bigger evidence (long chat logs, scan reports) goes to GridFS instead of the document
+const INLINE_EVIDENCE_LIMIT: usize = 16 * 1024;
+const EVIDENCE_BUCKET: &str = "evidence";
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "kind", content = "value")]
+pub enum Evidence {
+    ScreenshotUrl(String),
+    ChatLog(String),
+    Replay(#[serde(serialize_with = "ReplayId::serialize_number", deserialize_with = "ReplayId::deserialize_number")] ReplayId),
+    Asset(u64),
+    // What sanitizing the offending model found in it
+    ScanReport(Vec<SanitizeChange>)
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EvidenceKind {
+    ScreenshotUrl,
+    ChatLog,
+    Replay,
+    Asset,
+    ScanReport
+}
+
+impl Evidence {
+    pub fn kind(&self) -> EvidenceKind {
+        match self {
+            Evidence::ScreenshotUrl(_) => EvidenceKind::ScreenshotUrl,
+            Evidence::ChatLog(_) => EvidenceKind::ChatLog,
+            Evidence::Replay(_) => EvidenceKind::Replay,
+            Evidence::Asset(_) => EvidenceKind::Asset,
+            Evidence::ScanReport(_) => EvidenceKind::ScanReport
+        }
+    }
+}
+
+// Who gets to see a piece of evidence, each level includes the ones after it
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum EvidenceVisibility {
+    // Also shown to the moderated player, e.g. for their appeal
+    Player,
+    Moderators,
+    Administrators
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EvidenceViewer {
+    Player(u64),
+    Moderator,
+    Administrator
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type")]
+pub enum EvidenceTarget {
+    // A ban is told apart from the player's earlier and later bans by when it was given, like appeals do
+    Ban {
+        #[serde(rename = "userId")]
+        user_id: i64,
+        #[serde(rename = "bannedTime", with = "chrono_datetime_as_bson_datetime")]
+        banned_time: DateTime<Utc>
+    },
+    Action {
+        #[serde(rename = "actionId")]
+        action_id: ObjectId
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EvidenceAttachment {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub target: EvidenceTarget,
+    // The moderated player
+    #[serde(rename = "userId")]
+    pub user_id: i64,
+    pub kind: EvidenceKind,
+    pub visibility: EvidenceVisibility,
+    #[serde(rename = "addedBy")]
+    pub added_by: String,
+    #[serde(rename = "addedTime", with = "chrono_datetime_as_bson_datetime")]
+    pub added_time: DateTime<Utc>,
+    // None when the evidence was too big to keep inline, use `get_evidence_content`
+    pub content: Option<Evidence>,
+    #[serde(rename = "blobId")]
+    pub blob_id: Option<ObjectId>
+}
+
+impl EvidenceAttachment {
+    pub fn is_visible_to(&self, viewer: EvidenceViewer) -> bool {
+        match viewer {
+            EvidenceViewer::Player(user_id) => user_id as i64 == self.user_id && self.visibility == EvidenceVisibility::Player,
+            EvidenceViewer::Moderator => self.visibility <= EvidenceVisibility::Moderators,
+            EvidenceViewer::Administrator => true
+        }
+    }
+}
+
+impl Backend {
+    fn evidence_collection(&self) -> Collection<EvidenceAttachment> {
+        self.get_database().collection("moderationevidence")
+    }
+
+    fn evidence_bucket(&self) -> GridFsBucket {
+        let options = GridFsBucketOptions::builder()
+            .bucket_name(EVIDENCE_BUCKET.to_string())
+            .build();
+
+        self.get_database().gridfs_bucket(options)
+    }
+
+    pub async fn add_evidence(&self, target: EvidenceTarget, evidence: Evidence, visibility: EvidenceVisibility, added_by: &str) -> Result<EvidenceAttachment, Box<dyn std::error::Error>> {
+        let user_id = match &target {
+            EvidenceTarget::Ban { user_id, banned_time } => match self.find_ban_entry(*user_id as u64).await? {
+                Some(ban) if ban.banned_time == *banned_time => *user_id,
+                _ => return Err("The ban is no longer in place.".into())
+            },
+            EvidenceTarget::Action { action_id } => match self.find_moderation_action(*action_id).await? {
+                Some(action) => action.user_id,
+                None => return Err("Moderation action does not exist.".into())
+            }
+        };
+
+        let kind = evidence.kind();
+        let (content, blob_id) = if to_vec(&doc! { "content": to_bson(&evidence)? })?.len() > INLINE_EVIDENCE_LIMIT {
+            let bytes = serde_json::to_vec(&evidence)?;
+            let blob_id = self.evidence_bucket()
+                .upload_from_futures_0_3_reader(format!("{}_{:?}", user_id, kind), Cursor::new(bytes), None)
+                .await?;
+            (None, Some(blob_id))
+        } else {
+            (Some(evidence), None)
+        };
+
+        let mut attachment = EvidenceAttachment {
+            id: None,
+            target,
+            user_id,
+            kind,
+            visibility,
+            added_by: added_by.to_string(),
+            added_time: Utc::now(),
+            content,
+            blob_id
+        };
+        let result = self.evidence_collection().insert_one(&attachment, None).await?;
+        attachment.id = result.inserted_id.as_object_id();
+
+        Ok(attachment)
+    }
+
+    // Only the attachments the viewer is allowed to see, oldest first
+    pub async fn list_evidence(&self, target: &EvidenceTarget, viewer: EvidenceViewer) -> Result<Vec<EvidenceAttachment>, Box<dyn std::error::Error>> {
+        let options = FindOptions::builder().sort(doc! { "addedTime": 1 }).build();
+        let mut cursor = self.evidence_collection().find(doc! { "target": to_bson(target)? }, options).await?;
+
+        let mut result: Vec<EvidenceAttachment> = Vec::new();
+        while let Some(attachment) = cursor.next().await {
+            let attachment = attachment?;
+            if attachment.is_visible_to(viewer) {
+                result.push(attachment);
+            }
+        }
+
+        Ok(result)
+    }
+
+    // Loads evidence kept in GridFS as well as inline evidence
+    pub async fn get_evidence_content(&self, attachment_id: ObjectId, viewer: EvidenceViewer) -> Result<Evidence, Box<dyn std::error::Error>> {
+        let attachment = match self.evidence_collection().find_one(doc! { "_id": attachment_id }, None).await? {
+            Some(attachment) if attachment.is_visible_to(viewer) => attachment,
+            // Evidence the viewer can't see is reported the same as missing evidence
+            _ => return Err("Evidence does not exist.".into())
+        };
+
+        match (attachment.content, attachment.blob_id) {
+            (Some(content), _) => Ok(content),
+            (None, Some(blob_id)) => {
+                let mut bytes: Vec<u8> = Vec::new();
+                let mut stream = self.evidence_bucket().open_download_stream(blob_id.into()).await?;
+                stream.read_to_end(&mut bytes).await?;
+
+                Ok(serde_json::from_slice(&bytes)?)
+            },
+            (None, None) => Err("Evidence has no content.".into())
+        }
+    }
+
+    pub async fn remove_evidence(&self, attachment_id: ObjectId, viewer: EvidenceViewer) -> Result<(), Box<dyn std::error::Error>> {
+        if viewer != EvidenceViewer::Administrator {
+            return Err("Only administrators can remove evidence.".into())
+        }
+
+        if let Some(attachment) = self.evidence_collection().find_one(doc! { "_id": attachment_id }, None).await? {
+            if let Some(blob_id) = attachment.blob_id {
+                self.evidence_bucket().delete(blob_id.into()).await?;
+            }
+            self.evidence_collection().delete_one(doc! { "_id": attachment_id }, None).await?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,193 @@
+use std::collections::{HashSet, VecDeque};
+use chrono::{DateTime, Utc};
+use futures::stream::StreamExt;
+use mongodb::{bson::{doc, serde_helpers::chrono_datetime_as_bson_datetime, to_bson}, Collection};
+use serde::{Deserialize, Serialize};
+
+use crate::Backend;
+use crate::database::api_keys::ApiKey;
+use crate::database::moderation::{BanDuration, BanEntry};
+
+// Stops runaway traversals through badly linked data
+const MAX_LINKED_IDENTITIES: usize = 256;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+#[serde(tag = "type", content = "id")]
+pub enum Identity {
+    Roblox(i64),
+    Discord(i64),
+    ApiKey(String)
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct IdentityLink {
+    pub first: Identity,
+    pub second: Identity,
+    #[serde(rename = "linkedBy")]
+    pub linked_by: String,
+    #[serde(rename = "linkedTime", with = "chrono_datetime_as_bson_datetime")]
+    pub linked_time: DateTime<Utc>
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct BanCheck {
+    pub ban: Option<BanEntry>,
+    // Active bans of other Roblox accounts linked to the user
+    #[serde(rename = "linkedBans")]
+    pub linked_bans: Vec<BanEntry>
+}
+
+impl BanCheck {
+    pub fn is_banned(&self) -> bool {
+        self.ban.is_some()
+    }
+
+    pub fn is_linked_to_banned_account(&self) -> bool {
+        !self.linked_bans.is_empty()
+    }
+}
+
+impl Backend {
+    fn identity_links_collection(&self) -> Collection<IdentityLink> {
+        self.get_database().collection("identitylinks")
+    }
+
+    pub async fn link_identities(&self, first: Identity, second: Identity, linked_by: &str) -> Result<(), Box<dyn std::error::Error>> {
+        if first == second {
+            return Err("An identity cannot be linked to itself.".into())
+        }
+        if self.find_identity_link(&first, &second).await?.is_some() {
+            return Ok(())
+        }
+
+        self.identity_links_collection().insert_one(IdentityLink {
+            first,
+            second,
+            linked_by: linked_by.to_string(),
+            linked_time: Utc::now()
+        }, None).await?;
+
+        Ok(())
+    }
+
+    pub async fn unlink_identities(&self, first: &Identity, second: &Identity) -> Result<(), Box<dyn std::error::Error>> {
+        self.identity_links_collection().delete_many(doc! {
+            "$or": [
+                { "first": to_bson(first)?, "second": to_bson(second)? },
+                { "first": to_bson(second)?, "second": to_bson(first)? }
+            ]
+        }, None).await?;
+
+        Ok(())
+    }
+
+    async fn find_identity_link(&self, first: &Identity, second: &Identity) -> Result<Option<IdentityLink>, Box<dyn std::error::Error>> {
+        Ok(self.identity_links_collection().find_one(doc! {
+            "$or": [
+                { "first": to_bson(first)?, "second": to_bson(second)? },
+                { "first": to_bson(second)?, "second": to_bson(first)? }
+            ]
+        }, None).await?)
+    }
+
+    // Identities linked to this one directly, through recorded links or through an API key's owner and Discord user
+    async fn directly_linked_identities(&self, identity: &Identity) -> Result<Vec<Identity>, Box<dyn std::error::Error>> {
+        let mut linked: Vec<Identity> = Vec::new();
+
+        let identity_bson = to_bson(identity)?;
+        let mut cursor = self.identity_links_collection().find(doc! {
+            "$or": [{ "first": identity_bson.clone() }, { "second": identity_bson }]
+        }, None).await?;
+        while let Some(link) = cursor.next().await {
+            let link = link?;
+            linked.push(if link.first == *identity { link.second } else { link.first });
+        }
+
+        let api_keys_collection: Collection<ApiKey> = self.get_database().collection("apikeys");
+        let api_key_filter = match identity {
+            Identity::Roblox(user_id) => doc! { "assignOwner": user_id.to_string() },
+            Identity::Discord(discord_id) => doc! { "associatedDiscordUser": discord_id.to_string() },
+            Identity::ApiKey(value) => doc! { "value": value }
+        };
+        let mut cursor = api_keys_collection.find(api_key_filter, None).await?;
+        while let Some(api_key) = cursor.next().await {
+            let api_key = api_key?;
+            linked.push(Identity::ApiKey(api_key.value));
+            // Unassigned keys have "None" as their owner
+            if let Ok(user_id) = api_key.assign_owner.parse::<i64>() {
+                linked.push(Identity::Roblox(user_id));
+            }
+            if let Some(Ok(discord_id)) = api_key.associated_discord_user.map(|discord_id| discord_id.parse::<i64>()) {
+                linked.push(Identity::Discord(discord_id));
+            }
+        }
+
+        Ok(linked.into_iter().filter(|linked_identity| linked_identity != identity).collect())
+    }
+
+    // Everything reachable from the identity through any number of links, not including itself
+    pub async fn get_linked_identities(&self, identity: &Identity) -> Result<Vec<Identity>, Box<dyn std::error::Error>> {
+        let mut seen: HashSet<Identity> = HashSet::from([identity.clone()]);
+        let mut queue: VecDeque<Identity> = VecDeque::from([identity.clone()]);
+        let mut result: Vec<Identity> = Vec::new();
+
+        while let Some(current) = queue.pop_front() {
+            for linked in self.directly_linked_identities(&current).await? {
+                if seen.len() >= MAX_LINKED_IDENTITIES {
+                    return Ok(result)
+                }
+                if seen.insert(linked.clone()) {
+                    result.push(linked.clone());
+                    queue.push_back(linked);
+                }
+            }
+        }
+
+        Ok(result)
+    }
+
+    // Returns the linked accounts that were banned and the API keys that were disabled.
+    // Keys are only disabled for permanent bans, since nothing enables them again once a timed ban runs out.
+    pub(crate) async fn propagate_ban(&self, user_id: u64, duration: BanDuration, moderator: &str, reason: &str) -> Result<Vec<Identity>, Box<dyn std::error::Error>> {
+        let linked_reason = format!("Linked to banned account {}: {}", user_id, reason);
+        let api_keys_collection: Collection<ApiKey> = self.get_database().collection("apikeys");
+
+        let mut affected: Vec<Identity> = Vec::new();
+        for identity in self.get_linked_identities(&Identity::Roblox(user_id as i64)).await? {
+            let was_affected = match &identity {
+                // A longer ban the linked account already has is left alone, reason included
+                Identity::Roblox(linked_user_id) => self.extend_ban_entry(*linked_user_id as u64, duration, moderator, &linked_reason).await?,
+                Identity::ApiKey(value) if duration == BanDuration::Permanent => {
+                    let disable = doc! { "$set": { "enabled": false } };
+                    api_keys_collection.update_one(doc! { "value": value, "enabled": true }, disable, None).await?.modified_count > 0
+                },
+                Identity::ApiKey(_) => false,
+                // Discord accounts have no bans of their own, they're covered by `check_ban` through their links
+                Identity::Discord(_) => false
+            };
+
+            if was_affected {
+                affected.push(identity);
+            }
+        }
+
+        Ok(affected)
+    }
+
+    // Fails while the user's or a linked account's ban is still waiting on `migrate_time_fields`
+    pub async fn check_ban(&self, user_id: u64) -> Result<BanCheck, Box<dyn std::error::Error>> {
+        let time_now = Utc::now();
+        let ban = self.find_ban_entry(user_id).await?.filter(|ban| ban.is_active(time_now));
+
+        let mut linked_bans: Vec<BanEntry> = Vec::new();
+        for identity in self.get_linked_identities(&Identity::Roblox(user_id as i64)).await? {
+            if let Identity::Roblox(linked_user_id) = identity {
+                if let Some(linked_ban) = self.find_ban_entry(linked_user_id as u64).await?.filter(|ban| ban.is_active(time_now)) {
+                    linked_bans.push(linked_ban);
+                }
+            }
+        }
+
+        Ok(BanCheck { ban, linked_bans })
+    }
+}
@@ -0,0 +1,72 @@
+use mongodb::{bson::{doc, Bson, Document}, Collection};
+
+use crate::Backend;
+
+// (collection, field) pairs that used to hold epoch milliseconds and are now BSON dates.
+// Bans are converted on their own, together with the repair of their durations.
+const TIME_FIELDS: [(&str, &str); 9] = [
+    ("moderationactions", "createdTime"),
+    ("moderationactions", "expiresTime"),
+    ("banappeals", "banTime"),
+    ("banappeals", "submittedTime"),
+    ("banappeals", "resolvedTime"),
+    ("moderationevidence", "addedTime"),
+    ("identitylinks", "linkedTime"),
+    ("whitelistedassets", "approvedTime"),
+    ("apikeys", "timeCreated")
+];
+
+impl Backend {
+    // Converts documents written before times were stored as BSON dates, returns how many were changed.
+    // Already converted documents are left alone, so it's safe to run more than once.
+    // `repair_ban_durations` stretches old timed bans by 1000x, since `ban_player` used to add seconds to milliseconds.
+    // Only use it when every timed ban on record went through `ban_player`, imported and shortened bans were stored correctly.
+    // Until this has run, `find_ban_entry` and `check_ban` fail for unconverted bans and `get_ban_collection` leaves them out.
+    pub async fn migrate_time_fields(&self, repair_ban_durations: bool) -> Result<u64, Box<dyn std::error::Error>> {
+        let database = self.get_database();
+        let mut modified: u64 = 0;
+
+        // The repair and the conversion happen in the same update, so a converted ban is never repaired twice
+        let banned_until = match repair_ban_durations {
+            true => Bson::Document(doc! { "$cond": [
+                { "$isNumber": "$bannedTime" },
+                { "$add": ["$bannedTime", { "$multiply": [{ "$subtract": ["$bannedUntil", "$bannedTime"] }, 1000] }] },
+                "$bannedUntil"
+            ] }),
+            false => Bson::String("$bannedUntil".to_string())
+        };
+        let convert_bans = vec![doc! { "$set": {
+            "bannedUntil": { "$switch": {
+                "branches": [
+                    // -1 was permanent, which is now a missing end
+                    { "case": { "$eq": ["$bannedUntil", -1] }, "then": Bson::Null },
+                    { "case": { "$isNumber": "$bannedUntil" }, "then": { "$toDate": banned_until } }
+                ],
+                "default": "$bannedUntil"
+            } },
+            "bannedTime": { "$cond": [{ "$isNumber": "$bannedTime" }, { "$toDate": "$bannedTime" }, "$bannedTime"] }
+        } }];
+        let bans_collection: Collection<Document> = database.collection("bannedplayers");
+        modified += bans_collection.update_many(doc! { "$or": [
+            { "bannedTime": { "$type": "number" } },
+            { "bannedUntil": { "$type": "number" } }
+        ] }, convert_bans, None).await?.modified_count;
+
+        for (collection_name, field) in TIME_FIELDS {
+            let collection: Collection<Document> = database.collection(collection_name);
+            let convert = vec![doc! { "$set": { field: { "$toDate": format!("${}", field) } } }];
+            modified += collection.update_many(doc! { field: { "$type": "number" } }, convert, None).await?.modified_count;
+        }
+
+        // Appeal events are kept in an array on the appeal
+        let appeals_collection: Collection<Document> = database.collection("banappeals");
+        let convert_history = vec![doc! { "$set": { "history": { "$map": {
+            "input": "$history",
+            "as": "event",
+            "in": { "$mergeObjects": ["$$event", { "time": { "$cond": [{ "$isNumber": "$$event.time" }, { "$toDate": "$$event.time" }, "$$event.time"] } }] }
+        } } } }];
+        modified += appeals_collection.update_many(doc! { "history.time": { "$type": "number" } }, convert_history, None).await?.modified_count;
+
+        Ok(modified)
+    }
+}
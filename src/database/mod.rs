@@ -2,8 +2,33 @@ use mongodb::Database;
 
 use crate::Backend;
 
+pub mod actions;
 pub mod api_keys;
+pub mod appeals;
+pub mod ban_lists;
+pub mod evidence;
+pub mod links;
+pub mod migrations;
 pub mod moderation;
+pub mod whitelist;
+
+// Times are stored as BSON dates, `bson::serde_helpers::chrono_datetime_as_bson_datetime` only covers required ones
+pub(crate) mod optional_bson_datetime {
+    use chrono::{DateTime, Utc};
+    use mongodb::bson::{self, serde_helpers::chrono_datetime_as_bson_datetime};
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &Option<DateTime<Utc>>, serializer: S) -> Result<S::Ok, S::Error> {
+        match value {
+            Some(value) => chrono_datetime_as_bson_datetime::serialize(value, serializer),
+            None => serializer.serialize_none()
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<DateTime<Utc>>, D::Error> {
+        Ok(Option::<bson::DateTime>::deserialize(deserializer)?.map(|value| value.to_chrono()))
+    }
+}
 
 impl Backend {
     pub fn get_database(&self) -> Database {
@@ -1,24 +1,75 @@
-use mongodb::{bson::doc, Collection};
+use chrono::{DateTime, Duration, Utc};
+use mongodb::{bson::{doc, serde_helpers::chrono_datetime_as_bson_datetime, Bson}, Collection};
 use serde::{ Deserialize, Serialize };
 use futures::stream::StreamExt;
 
 use crate::Backend;
-use crate::utils::datetime_now;
+use crate::database::links::Identity;
+use crate::database::optional_bson_datetime;
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct BanEntry {
     #[serde(rename = "userId")]
     pub user_id: i64,
-    #[serde(rename = "bannedTime")]
-    pub banned_time: i64,
-    #[serde(rename = "bannedUntil")]
-    pub banned_until: i64,
+    #[serde(rename = "bannedTime", with = "chrono_datetime_as_bson_datetime")]
+    pub banned_time: DateTime<Utc>,
+    // None is permanent
+    #[serde(rename = "bannedUntil", with = "optional_bson_datetime")]
+    pub banned_until: Option<DateTime<Utc>>,
     pub moderator: String,
     pub reason: String
 }
 
+impl BanEntry {
+    pub fn is_active(&self, time_now: DateTime<Utc>) -> bool {
+        self.banned_until.map(|banned_until| banned_until > time_now).unwrap_or(true)
+    }
+}
+
+// Whether a ban ending at `banned_until` outlasts one ending at `than`, permanent bans (None) outlast everything
+pub(crate) fn is_longer_ban(banned_until: Option<DateTime<Utc>>, than: Option<DateTime<Utc>>) -> bool {
+    match (banned_until, than) {
+        (_, None) => false,
+        (None, _) => true,
+        (Some(banned_until), Some(than)) => banned_until > than
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BanDuration {
+    Permanent,
+    For(Duration)
+}
+
+impl BanDuration {
+    // None is permanent, which is also what a duration too long to represent amounts to
+    pub fn ends_at(&self, from: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        match self {
+            BanDuration::Permanent => None,
+            BanDuration::For(duration) => from.checked_add_signed(*duration)
+        }
+    }
+
+    // A ban that's over before it starts is always a mistake
+    pub fn validate(&self) -> Result<(), Box<dyn std::error::Error>> {
+        match self {
+            BanDuration::For(duration) if *duration <= Duration::zero() => Err("Ban duration must be positive.".into()),
+            _ => Ok(())
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct BanListingEntry {
+    #[serde(flatten)]
+    pub entry: BanEntry,
+    // None when the user couldn't be looked up on Roblox
+    pub username: Option<String>
+}
+
 impl Backend {
     // Note: + Send because #[OpenApi] complain about not being able to send between threads safely
+    // Bans that can't be read, e.g. ones `migrate_time_fields` hasn't converted yet, are left out
     pub async fn get_ban_collection(&self) -> Result<Vec<BanEntry>, Box<dyn std::error::Error>> {
         let database = self.get_database();
 
@@ -28,17 +79,31 @@ impl Backend {
         let mut result: Vec<BanEntry> = Vec::new();
 
         while let Some(stream) = cursor.next().await {
-            match stream {
-                Ok(document) => {
-                    result.push(document);
-                }
-                _ => {}
+            if let Ok(document) = stream {
+                result.push(document);
             }
         }
 
         Ok(result)
     }
 
+    pub async fn get_ban_collection_with_usernames(&self) -> Result<Vec<BanListingEntry>, Box<dyn std::error::Error>> {
+        let bans = self.get_ban_collection().await?;
+
+        let user_ids: Vec<u64> = bans.iter().map(|ban| ban.user_id as u64).collect();
+        // The listing is still useful without names, so a failed lookup leaves them all out
+        let mut usernames = self.get_usernames(&user_ids).await.unwrap_or_default();
+
+        Ok(bans
+            .into_iter()
+            .map(|ban| {
+                let username = usernames.remove(&(ban.user_id as u64));
+                BanListingEntry { entry: ban, username }
+            })
+            .collect())
+    }
+
+    // Errors for a ban `migrate_time_fields` hasn't converted yet, rather than treating the player as unbanned
     pub(crate) async fn find_ban_entry(&self, user_id: u64) -> Result<Option<BanEntry>, Box<dyn std::error::Error>> {
         let database = self.get_database();
 
@@ -54,17 +119,31 @@ impl Backend {
         Ok(result)
     }
 
-    pub async fn ban_player(&self, user_id: u64, duration_in_minutes: i32, moderator: &str, reason: &str) -> Result<(), Box<dyn std::error::Error>> {
+    // With `propagate_to_linked`, every Roblox account linked to the user is banned too, and for permanent bans linked
+    // API keys are disabled. Returns the linked identities that were changed. Unbanning doesn't undo any of it,
+    // use `unban_player` and `set_api_key_enabled` on them.
+    pub async fn ban_player(&self, user_id: u64, duration: BanDuration, moderator: &str, reason: &str, propagate_to_linked: bool) -> Result<Vec<Identity>, Box<dyn std::error::Error>> {
+        self.write_ban_entry(user_id, duration, moderator, reason).await?;
+
+        if propagate_to_linked {
+            return self.propagate_ban(user_id, duration, moderator, reason).await
+        }
+
+        Ok(Vec::new())
+    }
+
+    pub(crate) async fn write_ban_entry(&self, user_id: u64, duration: BanDuration, moderator: &str, reason: &str) -> Result<(), Box<dyn std::error::Error>> {
+        duration.validate()?;
         let database = self.get_database();
 
         let collection: Collection<BanEntry> = database.collection("bannedplayers");
 
-        let time_now: i64 = datetime_now() as i64;
-        let banned_until = if duration_in_minutes != -1 { time_now as i64 + (duration_in_minutes * 60) as i64} else { -1 };
+        let time_now = Utc::now();
+        let banned_until = duration.ends_at(time_now);
         if self.find_ban_entry(user_id).await?.is_some() {
             let update = doc! { "$set": doc! {
                 "bannedTime": time_now,
-                "bannedUntil": banned_until,
+                "bannedUntil": banned_until.map(Bson::from).unwrap_or(Bson::Null),
                 "moderator": moderator.to_string(),
                 "reason": reason.to_string()
             } };
@@ -76,7 +155,7 @@ impl Backend {
             collection.insert_one(BanEntry {
                 user_id: user_id as i64,
                 banned_time: time_now,
-                banned_until: banned_until,
+                banned_until,
                 moderator: moderator.to_string(),
                 reason: reason.to_string()
             }, None).await?;
@@ -85,6 +164,36 @@ impl Backend {
         Ok(())
     }
 
+    // Like `write_ban_entry`, but leaves a ban that lasts at least as long alone. Returns whether the ban was written.
+    pub(crate) async fn extend_ban_entry(&self, user_id: u64, duration: BanDuration, moderator: &str, reason: &str) -> Result<bool, Box<dyn std::error::Error>> {
+        if let Some(existing) = self.find_ban_entry(user_id).await? {
+            if !is_longer_ban(duration.ends_at(Utc::now()), existing.banned_until) {
+                return Ok(false)
+            }
+        }
+
+        self.write_ban_entry(user_id, duration, moderator, reason).await?;
+        Ok(true)
+    }
+
+    // Only ever brings the end of the ban closer
+    pub async fn shorten_ban(&self, user_id: u64, banned_until: DateTime<Utc>) -> Result<(), Box<dyn std::error::Error>> {
+        let database = self.get_database();
+
+        let collection: Collection<BanEntry> = database.collection("bannedplayers");
+
+        let update = doc! { "$set": doc! {
+            "bannedUntil": banned_until
+        } };
+
+        collection.update_one(doc! {
+            "userId": user_id as i64,
+            "$or": [{ "bannedUntil": Bson::Null }, { "bannedUntil": { "$gt": banned_until } }]
+        }, update, None).await?;
+
+        Ok(())
+    }
+
     pub async fn unban_player(&self, user_id: u64) -> Result<(), Box<dyn std::error::Error>> {
         let database = self.get_database();
 
@@ -97,4 +206,52 @@ impl Backend {
 
         Ok(())
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, Duration, Utc};
+    use super::{BanDuration, BanEntry};
+
+    fn time(rfc3339: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(rfc3339).unwrap().with_timezone(&Utc)
+    }
+
+    fn ban(banned_until: Option<DateTime<Utc>>) -> BanEntry {
+        BanEntry {
+            user_id: 1,
+            banned_time: time("2024-01-01T00:00:00Z"),
+            banned_until,
+            moderator: "moderator".to_string(),
+            reason: "reason".to_string()
+        }
+    }
+
+    #[test]
+    fn timed_bans_end_after_their_duration() {
+        let from = time("2024-01-01T00:00:00Z");
+
+        // Used to end 3.6 seconds in, from adding seconds to milliseconds
+        assert_eq!(BanDuration::For(Duration::minutes(60)).ends_at(from), Some(time("2024-01-01T01:00:00Z")));
+        assert_eq!(BanDuration::For(Duration::days(7)).ends_at(from), Some(time("2024-01-08T00:00:00Z")));
+        assert_eq!(BanDuration::Permanent.ends_at(from), None);
+        assert_eq!(BanDuration::For(Duration::max_value()).ends_at(from), None);
+    }
+
+    #[test]
+    fn only_positive_durations_are_valid() {
+        assert!(BanDuration::Permanent.validate().is_ok());
+        assert!(BanDuration::For(Duration::seconds(1)).validate().is_ok());
+        assert!(BanDuration::For(Duration::zero()).validate().is_err());
+        assert!(BanDuration::For(Duration::minutes(-60)).validate().is_err());
+    }
+
+    #[test]
+    fn bans_are_active_until_they_end() {
+        let hour_ban = ban(BanDuration::For(Duration::hours(1)).ends_at(time("2024-01-01T00:00:00Z")));
+
+        assert!(hour_ban.is_active(time("2024-01-01T00:00:04Z")));
+        assert!(hour_ban.is_active(time("2024-01-01T00:59:59Z")));
+        assert!(!hour_ban.is_active(time("2024-01-01T01:00:00Z")));
+        assert!(ban(None).is_active(time("2100-01-01T00:00:00Z")));
+    }
+}
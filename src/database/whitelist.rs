@@ -0,0 +1,69 @@
+use chrono::{DateTime, Utc};
+use mongodb::{bson::{doc, serde_helpers::chrono_datetime_as_bson_datetime}, Collection};
+use serde::{ Deserialize, Serialize };
+
+use crate::Backend;
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct WhitelistedAsset {
+    #[serde(rename = "assetId")]
+    pub asset_id: i64,
+    #[serde(rename = "approvedVersion")]
+    pub approved_version: Option<i64>,
+    #[serde(rename = "approvedBy")]
+    pub approved_by: i64,
+    #[serde(rename = "approvedTime", with = "chrono_datetime_as_bson_datetime")]
+    pub approved_time: DateTime<Utc>,
+    // Roblox user ID of the bot account that owns the asset
+    #[serde(rename = "purchasedBy")]
+    pub purchased_by: Option<i64>
+}
+
+impl Backend {
+    pub async fn find_whitelisted_asset(&self, asset_id: u64) -> Result<Option<WhitelistedAsset>, Box<dyn std::error::Error>> {
+        let database = self.get_database();
+
+        let collection: Collection<WhitelistedAsset> = database.collection("whitelistedassets");
+
+        let result = collection.find_one(
+            doc! {
+                "assetId": asset_id as i64
+            },
+            None
+        ).await?;
+
+        Ok(result)
+    }
+
+    pub async fn record_whitelisted_asset(&self, asset_id: u64, version: Option<u64>, approved_by: u64, purchased_by: Option<u64>) -> Result<(), Box<dyn std::error::Error>> {
+        let database = self.get_database();
+
+        let collection: Collection<WhitelistedAsset> = database.collection("whitelistedassets");
+
+        let time_now = Utc::now();
+        let approved_version = version.map(|version| version as i64);
+        let purchased_by = purchased_by.map(|user_id| user_id as i64);
+        if self.find_whitelisted_asset(asset_id).await?.is_some() {
+            let update = doc! { "$set": doc! {
+                "approvedVersion": approved_version,
+                "approvedBy": approved_by as i64,
+                "approvedTime": time_now,
+                "purchasedBy": purchased_by
+            } };
+
+            collection.update_one(doc! {
+                "assetId": asset_id as i64
+            }, update, None).await?;
+        } else {
+            collection.insert_one(WhitelistedAsset {
+                asset_id: asset_id as i64,
+                approved_version,
+                approved_by: approved_by as i64,
+                approved_time: time_now,
+                purchased_by
+            }, None).await?;
+        }
+
+        Ok(())
+    }
+}
@@ -1,66 +1,393 @@
-use crate::utils;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use sha2::{Digest, Sha256};
 
+const FEISTEL_ROUNDS: usize = 8;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IDConversionError {
+    InvalidAlphabet(String),
+    Empty,
+    // 0 has no digits in bijective numeration, so it has no shareable form
+    Zero,
+    // `position` is 1-based, as counted by whoever typed the ID
+    InvalidCharacter { character: char, position: usize },
+    Overflow,
+    ChecksumMismatch,
+    // A typed ID was given a shareable ID of another kind, e.g. a user ID where a map ID was expected
+    WrongPrefix { expected: &'static str },
+    // Typed IDs only serialize and parse inside `Backend::with_shareable_ids`, or when given a Backend
+    NotConfigured
+}
+
+impl fmt::Display for IDConversionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IDConversionError::InvalidAlphabet(reason) => write!(f, "ID alphabet is invalid: {}", reason),
+            IDConversionError::Empty => write!(f, "ID is empty."),
+            IDConversionError::Zero => write!(f, "ID 0 cannot be converted."),
+            IDConversionError::InvalidCharacter { character, position } => write!(f, "ID contains invalid character '{}' at position {}.", character, position),
+            IDConversionError::Overflow => write!(f, "ID is too large."),
+            IDConversionError::ChecksumMismatch => write!(f, "ID check character does not match, it was probably mistyped."),
+            IDConversionError::WrongPrefix { expected } => write!(f, "ID does not start with \"{}\".", expected),
+            IDConversionError::NotConfigured => write!(f, "Shareable IDs can only be converted with a Backend.")
+        }
+    }
+}
+
+impl std::error::Error for IDConversionError {}
+
+// How short IDs typed in by people are cleaned up before decoding, and how they are displayed
+#[derive(Debug, Clone)]
+pub struct IDNormalization {
+    // Needs an alphabet without characters that only differ by case
+    pub case_insensitive: bool,
+    // (typed, meant) pairs for characters outside of the alphabet, e.g. ('0', 'o') and ('1', 'l')
+    pub confusables: Vec<(char, char)>,
+    // Skipped when decoding, e.g. spaces and dashes
+    pub ignored_separators: Vec<char>,
+    // Displays IDs as groups of this many characters, like "abcd-efgh"
+    pub group_size: Option<usize>,
+    // Ignored when decoding while grouping is on
+    pub group_separator: char
+}
+
+impl Default for IDNormalization {
+    fn default() -> Self {
+        Self {
+            case_insensitive: false,
+            confusables: Vec::new(),
+            ignored_separators: Vec::new(),
+            group_size: None,
+            group_separator: '-'
+        }
+    }
+}
+
+// Converts between numeric IDs and short IDs using bijective base-N numeration over the alphabet,
+// least significant character first. Every ID from 1 to u64::MAX has exactly one short form.
+#[derive(Clone)]
 pub struct IDConverter {
-    alphabets: String,
-    numbers: String
+    alphabet: Vec<char>,
+    check_character: bool,
+    // Round keys of the Feistel network IDs go through before being converted, None keeps plain IDs
+    round_keys: Option<[u64; FEISTEL_ROUNDS]>,
+    normalization: IDNormalization,
+    // Characters outside of the alphabet mapped to the alphabet character they stand for
+    substitutions: HashMap<char, char>
 }
 
-impl IDConverter {
-    fn convert_base(&self, input: String, translator: &String, convert_translator: &String, shift_left: bool) -> String {
-        let input = input.as_str();
-        let translator = translator.as_str();
-        let convert_translator = convert_translator.as_str();
+thread_local! {
+    // The converter of the Backend whose `with_shareable_ids` is running on this thread
+    static SCOPED_CONVERTER: RefCell<Option<IDConverter>> = const { RefCell::new(None) };
+}
+
+// Puts back the converter of the enclosing scope, even when the scope panics
+struct ScopedConverterGuard(Option<IDConverter>);
+
+impl Drop for ScopedConverterGuard {
+    fn drop(&mut self) {
+        SCOPED_CONVERTER.with(|scoped| *scoped.borrow_mut() = self.0.take());
+    }
+}
+
+pub(crate) fn with_scoped_converter<T>(converter: &IDConverter, with: impl FnOnce() -> T) -> T {
+    let enclosing = SCOPED_CONVERTER.with(|scoped| scoped.borrow_mut().replace(converter.clone()));
+    let _guard = ScopedConverterGuard(enclosing);
+
+    with()
+}
 
-        let mut base_x: usize = 0;
-        let base_value: usize = translator.chars().count();
+pub(crate) fn with_shareable_converter<T>(convert: impl FnOnce(&IDConverter) -> Result<T, IDConversionError>) -> Result<T, IDConversionError> {
+    SCOPED_CONVERTER.with(|scoped| match scoped.borrow().as_ref() {
+        Some(converter) => convert(converter),
+        None => Err(IDConversionError::NotConfigured)
+    })
+}
 
-        for char in input.chars() {
-            let mut char_index = translator.chars().position(|c| c == char).unwrap_or(0) + 1;
-            char_index -= if shift_left { 1 } else { 0 };
+// splitmix64's finalizer, mixes the half with the round key so every output bit depends on every input bit
+fn feistel_round(half: u32, round_key: u64) -> u32 {
+    let mut x = (half as u64) ^ round_key;
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+    x ^= x >> 31;
+    (x >> 32) as u32
+}
 
-            base_x = base_x * base_value + char_index;
+impl IDConverter {
+    pub fn new(alphabet: &str) -> Result<Self, IDConversionError> {
+        let alphabet: Vec<char> = alphabet.chars().collect();
+        if alphabet.len() < 2 {
+            return Err(IDConversionError::InvalidAlphabet("it needs at least 2 characters".to_string()))
+        }
+        let unique: HashSet<&char> = alphabet.iter().collect();
+        if unique.len() != alphabet.len() {
+            return Err(IDConversionError::InvalidAlphabet("it contains duplicate characters".to_string()))
         }
 
-        if base_x != 0 {
-            let mut result = String::new();
-            let new_base_value: usize = convert_translator.chars().count();
+        Ok(Self {
+            alphabet,
+            check_character: false,
+            round_keys: None,
+            normalization: IDNormalization::default(),
+            substitutions: HashMap::new()
+        })
+    }
 
-            while base_x > 0 {
-                let mut translated_position = base_x % new_base_value;
-                translated_position -= if shift_left { 1 } else { 0 };
-                
-                let translated_char = convert_translator.chars().nth(translated_position);
-                let mut char_to_use: char = '0';
-                if translated_char.is_some() {
-                    char_to_use = translated_char.unwrap();
+    fn case_variants(character: char) -> Vec<char> {
+        character
+            .to_lowercase()
+            .chain(character.to_uppercase())
+            .filter(|variant| *variant != character)
+            .collect()
+    }
+
+    pub fn set_normalization(&mut self, normalization: IDNormalization) -> Result<(), IDConversionError> {
+        let mut substitutions: HashMap<char, char> = HashMap::new();
+
+        if normalization.case_insensitive {
+            for character in &self.alphabet {
+                for variant in Self::case_variants(*character) {
+                    if self.alphabet.contains(&variant) {
+                        return Err(IDConversionError::InvalidAlphabet(format!("'{}' and '{}' only differ by case", character, variant)))
+                    }
+                    substitutions.insert(variant, *character);
                 }
+            }
+        }
 
-                result.push(char_to_use);
-                base_x /= new_base_value;
+        for (typed, meant) in &normalization.confusables {
+            if !self.alphabet.contains(meant) {
+                return Err(IDConversionError::InvalidAlphabet(format!("confusable '{}' maps to '{}', which is not in the alphabet", typed, meant)))
+            }
+            if self.alphabet.contains(typed) {
+                return Err(IDConversionError::InvalidAlphabet(format!("confusable '{}' is already in the alphabet", typed)))
+            }
+            substitutions.insert(*typed, *meant);
+            if normalization.case_insensitive {
+                for variant in Self::case_variants(*typed) {
+                    if !self.alphabet.contains(&variant) {
+                        substitutions.insert(variant, *meant);
+                    }
+                }
             }
+        }
+
+        let group_separator = normalization.group_size.map(|_| &normalization.group_separator);
+        for separator in normalization.ignored_separators.iter().chain(group_separator) {
+            if self.alphabet.contains(separator) || substitutions.contains_key(separator) {
+                return Err(IDConversionError::InvalidAlphabet(format!("separator '{}' is also used in IDs", separator)))
+            }
+        }
+
+        self.normalization = normalization;
+        self.substitutions = substitutions;
+        Ok(())
+    }
+
+    fn is_separator(&self, character: char) -> bool {
+        (self.normalization.group_size.is_some() && character == self.normalization.group_separator)
+            || self.normalization.ignored_separators.contains(&character)
+    }
+
+    fn grouped(&self, characters: Vec<char>) -> String {
+        match self.normalization.group_size {
+            Some(group_size) if group_size > 0 => characters
+                .chunks(group_size)
+                .map(|group| group.iter().collect::<String>())
+                .collect::<Vec<String>>()
+                .join(&self.normalization.group_separator.to_string()),
+            _ => characters.into_iter().collect()
+        }
+    }
+
+    // Keyed IDs look random and can't be enumerated without the key, but short IDs made
+    // with another key (or none) decode to different numbers.
+    pub fn set_key(&mut self, key: Option<&[u8]>) {
+        self.round_keys = key.map(|key| {
+            let mut round_keys = [0u64; FEISTEL_ROUNDS];
+            for (round, round_key) in round_keys.iter_mut().enumerate() {
+                let digest = Sha256::new().chain_update(key).chain_update([round as u8]).finalize();
+                *round_key = u64::from_le_bytes(digest[..8].try_into().unwrap());
+            }
+            round_keys
+        });
+    }
 
-            utils::reverse_string(result.as_str())
-        } else {
-            String::from(convert_translator.chars().nth(0).unwrap())
+    fn feistel_encrypt(round_keys: &[u64; FEISTEL_ROUNDS], value: u64) -> u64 {
+        let (mut left, mut right) = ((value >> 32) as u32, value as u32);
+        for round_key in round_keys {
+            (left, right) = (right, left ^ feistel_round(right, *round_key));
         }
+        ((left as u64) << 32) | right as u64
     }
 
-    pub fn new(alphabets: &String, numbers: &String) -> Self {
-        Self { alphabets: alphabets.to_owned(), numbers: numbers.to_owned() }
+    fn feistel_decrypt(round_keys: &[u64; FEISTEL_ROUNDS], value: u64) -> u64 {
+        let (mut left, mut right) = ((value >> 32) as u32, value as u32);
+        for round_key in round_keys.iter().rev() {
+            (left, right) = (right ^ feistel_round(left, *round_key), left);
+        }
+        ((left as u64) << 32) | right as u64
+    }
+
+    // Cycle walking keeps 0 out of the permutation, since it has no short form
+    fn permute(&self, value: u64) -> u64 {
+        match &self.round_keys {
+            Some(round_keys) => {
+                let mut permuted = Self::feistel_encrypt(round_keys, value);
+                while permuted == 0 {
+                    permuted = Self::feistel_encrypt(round_keys, permuted);
+                }
+                permuted
+            },
+            None => value
+        }
+    }
+
+    fn unpermute(&self, value: u64) -> u64 {
+        match &self.round_keys {
+            Some(round_keys) => {
+                let mut unpermuted = Self::feistel_decrypt(round_keys, value);
+                while unpermuted == 0 {
+                    unpermuted = Self::feistel_decrypt(round_keys, unpermuted);
+                }
+                unpermuted
+            },
+            None => value
+        }
+    }
+
+    // Appends a Luhn mod N character, catching any single mistyped character and most swapped neighbours.
+    // Short IDs made without it are rejected once it's enabled.
+    pub fn set_check_character(&mut self, enabled: bool) {
+        self.check_character = enabled;
+    }
+
+    fn base(&self) -> u64 {
+        self.alphabet.len() as u64
+    }
+
+    fn luhn_sum(&self, digits: &[usize], double_first: bool) -> usize {
+        let base = self.alphabet.len();
+        let mut double = double_first;
+
+        // Luhn works from the rightmost character, which is the check character when there is one
+        digits.iter().rev().fold(0, |sum, digit| {
+            let addend = if double { digit * 2 } else { *digit };
+            double = !double;
+            sum + addend / base + addend % base
+        })
+    }
+
+    fn check_digit(&self, digits: &[usize]) -> usize {
+        let base = self.alphabet.len();
+        (base - self.luhn_sum(digits, true) % base) % base
+    }
+
+    pub fn to_short(&self, input: u64) -> Result<String, IDConversionError> {
+        if input == 0 {
+            return Err(IDConversionError::Zero)
+        }
+
+        let mut digits: Vec<usize> = Vec::new();
+        let mut value = self.permute(input);
+        while value > 0 {
+            value -= 1;
+            digits.push((value % self.base()) as usize);
+            value /= self.base();
+        }
+
+        if self.check_character {
+            digits.push(self.check_digit(&digits));
+        }
+
+        Ok(self.grouped(digits.into_iter().map(|digit| self.alphabet[digit]).collect()))
     }
 
-    pub fn to_short(&self, input: u64) -> Result<String, Box<dyn std::error::Error>> {
-        let input = input.to_string();
-        let converted_to_base = self.convert_base(input, &self.numbers, &self.alphabets, true);
-        Ok(utils::reverse_string(converted_to_base.as_str()))
+    pub fn to_number(&self, input: &str) -> Result<u64, IDConversionError> {
+        let mut digits: Vec<usize> = Vec::new();
+        for (index, character) in input.chars().enumerate() {
+            if self.is_separator(character) {
+                continue
+            }
+
+            let canonical = self.substitutions.get(&character).copied().unwrap_or(character);
+            match self.alphabet.iter().position(|c| *c == canonical) {
+                Some(digit) => digits.push(digit),
+                None => return Err(IDConversionError::InvalidCharacter { character, position: index + 1 })
+            }
+        }
+
+        if self.check_character {
+            if digits.len() < 2 {
+                return Err(IDConversionError::Empty)
+            }
+            if !self.luhn_sum(&digits, false).is_multiple_of(self.alphabet.len()) {
+                return Err(IDConversionError::ChecksumMismatch)
+            }
+            digits.pop();
+        }
+        if digits.is_empty() {
+            return Err(IDConversionError::Empty)
+        }
+
+        let value = digits.iter().rev().try_fold(0u64, |value, digit| {
+            value
+                .checked_mul(self.base())
+                .and_then(|value| value.checked_add(*digit as u64 + 1))
+                .ok_or(IDConversionError::Overflow)
+        })?;
+
+        Ok(self.unpermute(value))
     }
+}
 
-    pub fn to_number(&self, input: String) -> Result<u64, Box<dyn std::error::Error>> {
-        let converted_to_base = self.convert_base(utils::reverse_string(input.as_str()), &self.alphabets, &self.numbers, false);
-        let id_from_converted = converted_to_base.parse::<u64>();
-        match id_from_converted {
-            Ok(id) => Ok(id),
-            Err(_) => Err("Transformed ID is not a number. Input possibly error/corrupted.".into())
+// Decodes shareable IDs made before the converter became bijective, which were the ID's decimal digits (as
+// positions in the numbers alphabet) re-encoded in the alphabet, least significant first. Only decoding is kept.
+#[derive(Clone)]
+pub struct LegacyIDConverter {
+    alphabet: Vec<char>,
+    numbers: Vec<char>
+}
+
+impl LegacyIDConverter {
+    pub fn new(alphabet: &str, numbers: &str) -> Result<Self, IDConversionError> {
+        let numbers: Vec<char> = numbers.chars().collect();
+        if numbers.len() < 2 || numbers.iter().any(|number| !number.is_ascii_digit()) {
+            return Err(IDConversionError::InvalidAlphabet("the numbers alphabet needs at least 2 decimal digits".to_string()))
+        }
+
+        Ok(Self { alphabet: IDConverter::new(alphabet)?.alphabet, numbers })
+    }
+
+    pub fn to_number(&self, input: &str) -> Result<u64, IDConversionError> {
+        if input.is_empty() {
+            return Err(IDConversionError::Empty)
         }
+
+        let base = self.alphabet.len() as u64;
+        let mut value: u64 = 0;
+        let characters: Vec<char> = input.chars().collect();
+        for (index, character) in characters.iter().enumerate().rev() {
+            let digit = match self.alphabet.iter().position(|c| c == character) {
+                Some(position) => position as u64 + 1,
+                None => return Err(IDConversionError::InvalidCharacter { character: *character, position: index + 1 })
+            };
+            value = value
+                .checked_mul(base)
+                .and_then(|value| value.checked_add(digit))
+                .ok_or(IDConversionError::Overflow)?;
+        }
+
+        // The value is the position of every decimal digit in the numbers alphabet
+        let numbers_base = self.numbers.len() as u64;
+        let mut decimal: Vec<char> = Vec::new();
+        while value > 0 {
+            decimal.push(self.numbers[(value % numbers_base) as usize]);
+            value /= numbers_base;
+        }
+
+        decimal.iter().rev().collect::<String>().parse::<u64>().map_err(|_| IDConversionError::Overflow)
     }
-}
\ No newline at end of file
+}
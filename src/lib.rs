@@ -1,30 +1,69 @@
+use std::time::Duration;
 use mongodb::{Client, options::ClientOptions};
-use id_converter::IDConverter;
+use database::actions::EscalationRule;
+use database::appeals::DEFAULT_APPEAL_COOLDOWN;
+use id_converter::{with_scoped_converter, IDConverter, LegacyIDConverter};
+use roblox::accounts::AccountPool;
+use roblox::cache::AssetCache;
+use roblox::download::DEFAULT_MAX_ASSET_DOWNLOAD_SIZE;
+use roblox::groups::GroupWhitelistPolicy;
+use roblox::http::{build_http_client, HttpConfig};
+use roblox::scheduler::{RequestScheduler, SchedulerConfig};
+use roblox::structs::AssetType;
+use roblox::users::UserCache;
 
 pub mod roblox;
 pub mod database;
 pub mod luau;
+pub mod shareable_ids;
 mod id_converter;
 mod utils;
+
+pub use id_converter::{IDConversionError, IDNormalization};
+
 pub struct Backend {
-    pub(crate) roblox_cookie: String,
-    pub(crate) roblox_xcsrf_token: String,
+    pub(crate) accounts: AccountPool,
     pub(crate) id_generator: IDConverter,
-    pub(crate) mongo_client: Option<Client>
+    pub(crate) legacy_id_generator: Option<LegacyIDConverter>,
+    pub(crate) mongo_client: Option<Client>,
+    pub(crate) asset_cache: Option<AssetCache>,
+    pub(crate) scheduler: RequestScheduler,
+    pub(crate) http_client: reqwest::Client,
+    pub(crate) max_asset_download_size: Option<u64>,
+    pub(crate) group_whitelist_policy: GroupWhitelistPolicy,
+    pub(crate) whitelistable_asset_types: Vec<AssetType>,
+    pub(crate) user_cache: UserCache,
+    pub(crate) escalation_rules: Vec<EscalationRule>,
+    pub(crate) appeal_cooldown: Duration
 }
 
 impl Backend {
-    #[allow(unused_must_use)]
     pub fn new(roblox_cookie: String, id_generator_alphabets: Vec<String>) -> Self {
-        if id_generator_alphabets.len() < 2 {
-            panic!("ID Generator must have at least 2 alphabets.");
+        // The second alphabet holds the digits shareable IDs used to be made from. New IDs don't need it,
+        // but when given, IDs made the old way can still be decoded with `get_legacy_number_id`.
+        if id_generator_alphabets.is_empty() {
+            panic!("ID Generator must have at least 1 alphabet.");
         }
-        let id_generator = IDConverter::new(&id_generator_alphabets[0], &id_generator_alphabets[1]);
+        let id_generator = IDConverter::new(&id_generator_alphabets[0]).expect("ID Generator alphabet is invalid.");
+        let legacy_id_generator = id_generator_alphabets
+            .get(1)
+            .map(|numbers| LegacyIDConverter::new(&id_generator_alphabets[0], numbers).expect("ID Generator numbers alphabet is invalid."));
 
-        let mut backend_self = Self { roblox_cookie: roblox_cookie, roblox_xcsrf_token: String::new(), id_generator: id_generator, mongo_client: None };
-        backend_self.refresh_xcsrf_token();
-
-        backend_self
+        Self {
+            accounts: AccountPool::new(roblox_cookie),
+            id_generator,
+            legacy_id_generator,
+            mongo_client: None,
+            asset_cache: None,
+            scheduler: RequestScheduler::new(SchedulerConfig::default()),
+            http_client: build_http_client(&HttpConfig::default()).expect("Default HTTP client could not be built."),
+            max_asset_download_size: Some(DEFAULT_MAX_ASSET_DOWNLOAD_SIZE),
+            group_whitelist_policy: GroupWhitelistPolicy::default(),
+            whitelistable_asset_types: vec![AssetType::Model],
+            user_cache: UserCache::default(),
+            escalation_rules: Vec::new(),
+            appeal_cooldown: DEFAULT_APPEAL_COOLDOWN
+        }
     } 
     
     pub async fn connect_mongodb(&mut self, mongodb_url: String, default_database: Option<String>) -> Result<(), Box<dyn std::error::Error>> {
@@ -39,12 +78,43 @@ impl Backend {
     pub fn get_shareable_id(&self, id: String) -> Result<String, Box<dyn std::error::Error>> {
         let parsed_id = id.parse::<u64>();
         match parsed_id {
-            Ok(i) => self.id_generator.to_short(i),
+            Ok(i) => Ok(self.id_generator.to_short(i)?),
             Err(_) => Err("ID cannot be converted into integer.".into())
         }
     }
 
+    // Errors are `IDConversionError`s, which say where the shareable ID went wrong
     pub fn get_number_id(&self, id: String) -> Result<u64, Box<dyn std::error::Error>> {
-        self.id_generator.to_number(id)
+        Ok(self.id_generator.to_number(&id)?)
+    }
+
+    // Shareable IDs from before the bijective converter. They only decode the same with `get_number_id` when the
+    // numbers alphabet was "0123456789" and neither a key nor the check character is set, so decode them here instead.
+    pub fn get_legacy_number_id(&self, id: String) -> Result<u64, Box<dyn std::error::Error>> {
+        match &self.legacy_id_generator {
+            Some(legacy_id_generator) => Ok(legacy_id_generator.to_number(&id)?),
+            None => Err("Legacy IDs need the numbers alphabet as the second ID Generator alphabet.".into())
+        }
+    }
+
+    pub fn set_shareable_id_check_character(&mut self, enabled: bool) {
+        self.id_generator.set_check_character(enabled);
+    }
+
+    // None keeps the plain IDs, which anyone can enumerate but which stay compatible with ones already shared
+    pub fn set_shareable_id_key(&mut self, secret_key: Option<&str>) {
+        self.id_generator.set_key(secret_key.map(|key| key.as_bytes()));
+    }
+
+    // Applies to both directions: displayed IDs are grouped, typed ones are cleaned up before decoding
+    pub fn set_shareable_id_normalization(&mut self, normalization: IDNormalization) -> Result<(), Box<dyn std::error::Error>> {
+        self.id_generator.set_normalization(normalization)?;
+        Ok(())
+    }
+
+    // Typed IDs display, serialize and parse as this backend's shareable IDs inside `with`, and fail to outside of it.
+    // The scope is per thread, so keep `.await`s out of `with`.
+    pub fn with_shareable_ids<T>(&self, with: impl FnOnce() -> T) -> T {
+        with_scoped_converter(&self.id_generator, with)
     }
 }
\ No newline at end of file
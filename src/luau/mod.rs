@@ -1,5 +1,5 @@
 use std::collections::HashMap;
-use full_moon::{ast::{Ast, Block, Prefix, Stmt, Suffix}, node::Node};
+use full_moon::{ast::{Ast, FunctionCall, Prefix, Suffix, Var}, node::Node, tokenizer::TokenReference, visitors::Visitor};
 use crate::Backend;
 
 type Range = (usize, usize);
@@ -9,58 +9,70 @@ fn range<N: Node>(node: N) -> (usize, usize) {
     (start.bytes(), end.bytes())
 }
 
-fn internal_find_from_visit<'a>(function_to_find: &str, block: &'a Block, usage_map: &mut HashMap<Range, Vec<&'a Suffix>>) {
-    for stmt in block.stmts() {
-        match stmt {
-            Stmt::Do(node) => {
-                internal_find_from_visit(function_to_find, node.block(), usage_map);
+// Walks every node of the AST, including else branches, returns, function expressions and call arguments.
+// There's no scope analysis, so a local shadowing the global is reported as well.
+struct GlobalUsageVisitor<'n> {
+    name: &'n str,
+    calls: HashMap<Range, Vec<Suffix>>,
+    references: Vec<Range>
+}
+
+impl GlobalUsageVisitor<'_> {
+    fn is_global(&self, token: &TokenReference) -> bool {
+        token.token().to_string() == self.name
+    }
+}
+
+impl Visitor for GlobalUsageVisitor<'_> {
+    fn visit_function_call(&mut self, node: &FunctionCall) {
+        if let Prefix::Name(token) = node.prefix() {
+            if self.is_global(token) {
+                self.calls.insert(range(token), node.suffixes().cloned().collect());
             }
-            Stmt::FunctionCall(node) => {
-                match node.prefix() {
-                    Prefix::Name(token) => {
-                        if &token.token().to_string() == function_to_find {
-                            usage_map.insert(range(token), node.suffixes().collect());
-                        }
-                    },
-                    _ => {}
-                };
-            },
-            Stmt::FunctionDeclaration(node) => {
-                internal_find_from_visit(function_to_find, node.body().block(), usage_map);
+        }
+    }
+
+    // Calls and indexing, e.g. `loadstring(s)` and `loadstring.x`
+    fn visit_prefix(&mut self, node: &Prefix) {
+        if let Prefix::Name(token) = node {
+            if self.is_global(token) {
+                self.references.push(range(token));
             }
-            Stmt::GenericFor(node) => {
-                internal_find_from_visit(function_to_find, node.block(), usage_map);
-            },
-            Stmt::If(node) => {
-                internal_find_from_visit(function_to_find, node.block(), usage_map);
-            },
-            Stmt::LocalFunction(node) => {
-                internal_find_from_visit(function_to_find, node.body().block(), usage_map);
+        }
+    }
+
+    // Plain reads and writes, e.g. `local ls = loadstring`
+    fn visit_var(&mut self, node: &Var) {
+        if let Var::Name(token) = node {
+            if self.is_global(token) {
+                self.references.push(range(token));
             }
-            Stmt::NumericFor(node) => {
-                internal_find_from_visit(function_to_find, node.block(), usage_map);
-            },
-            Stmt::Repeat(node) => {
-                internal_find_from_visit(function_to_find, node.block(), usage_map);
-            },
-            Stmt::While(node) => {
-                internal_find_from_visit(function_to_find, node.block(), usage_map);
-            },
-            _ => {}
-        };
+        }
     }
 }
 
+fn visit_global_usage<'n>(ast: &Ast, name: &'n str) -> GlobalUsageVisitor<'n> {
+    let mut visitor = GlobalUsageVisitor { name, calls: HashMap::new(), references: Vec::new() };
+    visitor.visit_ast(ast);
+
+    visitor
+}
+
 impl Backend {
-    pub fn luau_ast_from_string(&self, source: &String) -> Result<Ast, Box<dyn std::error::Error>> {
+    pub fn luau_ast_from_string(&self, source: &str) -> Result<Ast, Box<dyn std::error::Error>> {
         Ok(full_moon::parse(source)?)
     }
 
-    pub fn luau_find_global_function_usage<'a>(&'a self, ast: &'a Ast, function_to_find: &str) -> HashMap<Range, Vec<&Suffix>> {
-        let mut usage_map: HashMap<Range, Vec<&Suffix>> = HashMap::new();
-        let block = ast.nodes();
-        internal_find_from_visit(function_to_find, block, &mut usage_map);
+    // Calls of the global, keyed by where its name is in the source
+    pub fn luau_find_global_function_usage(&self, ast: &Ast, function_to_find: &str) -> HashMap<Range, Vec<Suffix>> {
+        visit_global_usage(ast, function_to_find).calls
+    }
+
+    // Every reference to the global, called or not, in the order they appear in the source
+    pub fn luau_find_global_references(&self, ast: &Ast, global_to_find: &str) -> Vec<Range> {
+        let mut references = visit_global_usage(ast, global_to_find).references;
+        references.sort();
 
-        usage_map
+        references
     }
-}
\ No newline at end of file
+}
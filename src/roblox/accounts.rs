@@ -0,0 +1,191 @@
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use reqwest::header;
+use serde::{Deserialize, Serialize};
+use crate::Backend;
+use crate::utils::datetime_now;
+
+pub(crate) const XCSRF_HEADER: &str = "x-csrf-token";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum AccountRoutingStrategy {
+    #[default]
+    RoundRobin,
+    LeastRecentlyUsed,
+    // The same asset always goes through the same account while it's healthy, so the account
+    // that purchased an asset is also the one downloading it
+    StickyPerAsset
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AccountStatus {
+    pub index: usize,
+    pub healthy: bool,
+    #[serde(rename = "lastUsed")]
+    pub last_used: Option<u64>,
+    #[serde(rename = "lastError")]
+    pub last_error: Option<String>,
+    // Only known once the session has been checked with `roblox_session_status`
+    #[serde(rename = "userId")]
+    pub user_id: Option<u64>
+}
+
+pub(crate) struct RobloxAccount {
+    cookie: String,
+    xcsrf_token: Mutex<String>,
+    healthy: AtomicBool,
+    last_used: AtomicU64,
+    last_error: Mutex<Option<String>>,
+    user_id: Mutex<Option<u64>>
+}
+
+impl RobloxAccount {
+    fn new(cookie: String) -> Self {
+        Self {
+            cookie,
+            xcsrf_token: Mutex::new(String::new()),
+            healthy: AtomicBool::new(true),
+            last_used: AtomicU64::new(0),
+            last_error: Mutex::new(None),
+            user_id: Mutex::new(None)
+        }
+    }
+
+    pub(crate) fn headers(&self) -> Result<header::HeaderMap, Box<dyn std::error::Error>> {
+        let mut reqwest_headers = header::HeaderMap::new();
+
+        let xcsrf_token = self.xcsrf_token.lock().unwrap().clone();
+        if !xcsrf_token.is_empty() {
+            reqwest_headers.insert(XCSRF_HEADER, header::HeaderValue::from_str(&xcsrf_token)?);
+        }
+        let mut cookie_header = header::HeaderValue::from_str(&format!(".ROBLOSECURITY={}", self.cookie))?;
+        cookie_header.set_sensitive(true);
+        reqwest_headers.insert(header::COOKIE, cookie_header);
+
+        Ok(reqwest_headers)
+    }
+
+    // Returns false when the token didn't change, so there's no point in retrying with it
+    pub(crate) fn update_xcsrf_token(&self, token: &str) -> bool {
+        let mut xcsrf_token = self.xcsrf_token.lock().unwrap();
+        if *xcsrf_token == token {
+            return false
+        }
+        *xcsrf_token = token.to_string();
+        true
+    }
+
+    pub(crate) fn mark_unhealthy(&self, reason: &str) {
+        self.healthy.store(false, Ordering::SeqCst);
+        *self.last_error.lock().unwrap() = Some(reason.to_string());
+    }
+
+    pub(crate) fn mark_healthy(&self) {
+        self.healthy.store(true, Ordering::SeqCst);
+        *self.last_error.lock().unwrap() = None;
+    }
+
+    pub(crate) fn user_id(&self) -> Option<u64> {
+        *self.user_id.lock().unwrap()
+    }
+
+    pub(crate) fn set_user_id(&self, user_id: u64) {
+        *self.user_id.lock().unwrap() = Some(user_id);
+    }
+
+    fn is_healthy(&self) -> bool {
+        self.healthy.load(Ordering::SeqCst)
+    }
+}
+
+pub(crate) struct AccountPool {
+    accounts: Vec<RobloxAccount>,
+    strategy: AccountRoutingStrategy,
+    next: AtomicUsize
+}
+
+impl AccountPool {
+    pub(crate) fn new(cookie: String) -> Self {
+        Self {
+            accounts: vec![RobloxAccount::new(cookie)],
+            strategy: AccountRoutingStrategy::default(),
+            next: AtomicUsize::new(0)
+        }
+    }
+
+    pub(crate) fn get(&self, index: usize) -> Option<&RobloxAccount> {
+        self.accounts.get(index)
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.accounts.len()
+    }
+
+    pub(crate) fn select(&self, asset_id: Option<u64>) -> Result<&RobloxAccount, Box<dyn std::error::Error>> {
+        let account_count = self.accounts.len();
+        let healthy = |index: &usize| self.accounts[*index].is_healthy();
+
+        let selected = match (self.strategy, asset_id) {
+            (AccountRoutingStrategy::StickyPerAsset, Some(asset_id)) => {
+                let start = (asset_id % account_count as u64) as usize;
+                (0..account_count).map(|offset| (start + offset) % account_count).find(healthy)
+            },
+            (AccountRoutingStrategy::LeastRecentlyUsed, _) => {
+                (0..account_count)
+                    .filter(healthy)
+                    .min_by_key(|index| self.accounts[*index].last_used.load(Ordering::SeqCst))
+            },
+            _ => {
+                let start = self.next.fetch_add(1, Ordering::SeqCst);
+                (0..account_count).map(|offset| (start + offset) % account_count).find(healthy)
+            }
+        };
+
+        match selected {
+            Some(index) => {
+                let account = &self.accounts[index];
+                account.last_used.store(datetime_now(), Ordering::SeqCst);
+                Ok(account)
+            },
+            None => Err("No healthy Roblox account available.".into())
+        }
+    }
+}
+
+impl Backend {
+    pub fn add_roblox_account(&mut self, roblox_cookie: String) {
+        self.accounts.accounts.push(RobloxAccount::new(roblox_cookie));
+    }
+
+    pub fn set_roblox_account_routing(&mut self, strategy: AccountRoutingStrategy) {
+        self.accounts.strategy = strategy;
+    }
+
+    pub fn roblox_account_statuses(&self) -> Vec<AccountStatus> {
+        self.accounts.accounts
+            .iter()
+            .enumerate()
+            .map(|(index, account)| {
+                let last_used = account.last_used.load(Ordering::SeqCst);
+                AccountStatus {
+                    index,
+                    healthy: account.is_healthy(),
+                    last_used: if last_used == 0 { None } else { Some(last_used) },
+                    last_error: account.last_error.lock().unwrap().clone(),
+                    user_id: account.user_id()
+                }
+            })
+            .collect()
+    }
+
+    // For putting an account back into rotation once its cookie has been sorted out
+    pub fn set_roblox_account_cookie(&mut self, index: usize, roblox_cookie: String) -> Result<(), Box<dyn std::error::Error>> {
+        match self.accounts.accounts.get_mut(index) {
+            Some(account) => {
+                *account = RobloxAccount::new(roblox_cookie);
+                Ok(())
+            },
+            None => Err("Roblox account does not exist.".into())
+        }
+    }
+}
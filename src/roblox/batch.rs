@@ -0,0 +1,50 @@
+use std::collections::{BTreeSet, HashMap};
+use futures::stream::{self, StreamExt};
+use crate::Backend;
+use super::structs::ItemDetails;
+
+// Requests without a batch endpoint fan out with at most this many in flight
+pub(super) const BATCH_CONCURRENCY: usize = 8;
+// The most items the catalog details endpoint accepts per request
+const CATALOG_BATCH_SIZE: usize = 120;
+
+pub(super) type BatchResult<T> = HashMap<u64, Result<T, Box<dyn std::error::Error>>>;
+
+impl Backend {
+    // Catalog items are looked up in batches, which don't include the public domain flag, creation dates or sales.
+    // Everything the catalog doesn't know about, e.g. models, falls back to one economy request per asset.
+    pub async fn fetch_assets_details(&self, asset_ids: &[u64]) -> BatchResult<ItemDetails> {
+        let unique_ids: Vec<u64> = asset_ids.iter().copied().collect::<BTreeSet<u64>>().into_iter().collect();
+
+        let mut details: BatchResult<ItemDetails> = HashMap::new();
+        for chunk in unique_ids.chunks(CATALOG_BATCH_SIZE) {
+            // A failed batch isn't fatal, its assets are fetched one by one below
+            if let Ok(items) = self.fetch_catalog_items_details_internal(chunk).await {
+                for item in items {
+                    details.insert(item.id, Ok(ItemDetails::from(item)));
+                }
+            }
+        }
+
+        let remaining: Vec<u64> = unique_ids.into_iter().filter(|asset_id| !details.contains_key(asset_id)).collect();
+        let fanned_out: BatchResult<ItemDetails> = stream::iter(remaining)
+            .map(|asset_id| async move { (asset_id, self.fetch_asset_details_internal(asset_id).await) })
+            .buffer_unordered(BATCH_CONCURRENCY)
+            .collect()
+            .await;
+
+        details.extend(fanned_out);
+        details
+    }
+
+    // The inventory is-owned endpoint has no batch variant
+    pub async fn user_owns_assets(&self, user_id: u64, asset_ids: &[u64]) -> BatchResult<bool> {
+        let unique_ids: BTreeSet<u64> = asset_ids.iter().copied().collect();
+
+        stream::iter(unique_ids)
+            .map(|asset_id| async move { (asset_id, self.user_own_asset_internal(user_id, asset_id).await) })
+            .buffer_unordered(BATCH_CONCURRENCY)
+            .collect()
+            .await
+    }
+}
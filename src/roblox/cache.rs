@@ -0,0 +1,309 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+use futures::io::{AsyncReadExt, Cursor};
+use futures::stream::StreamExt;
+use mongodb::{bson::doc, gridfs::GridFsBucket, options::{GridFsBucketOptions, GridFsUploadOptions}};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use crate::Backend;
+use crate::utils::datetime_now;
+
+const DEFAULT_GRIDFS_BUCKET: &str = "assetcache";
+
+#[derive(Debug, Clone)]
+pub enum AssetCacheStorage {
+    // Least recently used entries are evicted once `max_entries` is reached
+    Memory { max_entries: usize },
+    Directory(PathBuf),
+    // Stored in the default Mongo database, bucket name defaults to "assetcache"
+    GridFs { bucket_name: Option<String> }
+}
+
+#[derive(Debug, Clone)]
+pub struct AssetCacheConfig {
+    pub storage: AssetCacheStorage,
+    pub ttl: Option<Duration>,
+    // Assets bigger than this are still returned, just never cached
+    pub max_entry_size: usize
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CachedAsset {
+    #[serde(rename = "assetId")]
+    pub asset_id: u64,
+    // Version number, or what else was cached for the asset (e.g. "thumbnail_420x420_png")
+    pub variant: String,
+    pub sha256: String,
+    pub size: u64,
+    #[serde(rename = "cachedAt")]
+    pub cached_at: u64,
+    #[serde(skip)]
+    pub bytes: Vec<u8>
+}
+
+type AssetCacheKey = (u64, String);
+
+struct MemoryEntry {
+    asset: CachedAsset,
+    last_used: u64
+}
+
+#[derive(Default)]
+struct MemoryStorage {
+    entries: HashMap<AssetCacheKey, MemoryEntry>,
+    tick: u64
+}
+
+pub(crate) struct AssetCache {
+    config: AssetCacheConfig,
+    memory: Mutex<MemoryStorage>
+}
+
+pub(crate) fn sha256_hex(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+fn cache_file_stem(asset_id: u64, variant: &str) -> String {
+    format!("{}_{}", asset_id, variant)
+}
+
+impl AssetCache {
+    fn new(config: AssetCacheConfig) -> Self {
+        Self { config, memory: Mutex::new(MemoryStorage::default()) }
+    }
+
+    fn is_expired(&self, asset: &CachedAsset) -> bool {
+        match self.config.ttl {
+            Some(ttl) => datetime_now().saturating_sub(asset.cached_at) > ttl.as_millis() as u64,
+            None => false
+        }
+    }
+
+    fn is_valid(&self, asset: &CachedAsset) -> bool {
+        !self.is_expired(asset) && sha256_hex(&asset.bytes) == asset.sha256
+    }
+
+    fn memory_get(&self, key: AssetCacheKey) -> Option<CachedAsset> {
+        let mut memory = self.memory.lock().unwrap();
+        memory.tick += 1;
+        let tick = memory.tick;
+
+        let entry = memory.entries.get_mut(&key)?;
+        if self.is_expired(&entry.asset) {
+            memory.entries.remove(&key);
+            return None
+        }
+        entry.last_used = tick;
+        Some(entry.asset.clone())
+    }
+
+    fn memory_put(&self, max_entries: usize, asset: CachedAsset) {
+        let mut memory = self.memory.lock().unwrap();
+        memory.tick += 1;
+        let tick = memory.tick;
+
+        let key = (asset.asset_id, asset.variant.clone());
+        if !memory.entries.contains_key(&key) && memory.entries.len() >= max_entries {
+            let least_recently_used = memory.entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(key, _)| key.clone());
+            if let Some(evicted) = least_recently_used {
+                memory.entries.remove(&evicted);
+            }
+        }
+
+        if max_entries > 0 {
+            memory.entries.insert(key, MemoryEntry { asset, last_used: tick });
+        }
+    }
+
+    fn directory_get(&self, directory: &Path, key: AssetCacheKey) -> Result<Option<CachedAsset>, Box<dyn std::error::Error>> {
+        let stem = cache_file_stem(key.0, &key.1);
+        let metadata_path = directory.join(format!("{}.json", stem));
+        if !metadata_path.exists() {
+            return Ok(None)
+        }
+
+        let mut asset: CachedAsset = serde_json::from_str(&fs::read_to_string(&metadata_path)?)?;
+        asset.bytes = fs::read(directory.join(format!("{}.bin", stem)))?;
+
+        if !self.is_valid(&asset) {
+            fs::remove_file(&metadata_path)?;
+            return Ok(None)
+        }
+
+        Ok(Some(asset))
+    }
+
+    fn directory_put(&self, directory: &Path, asset: &CachedAsset) -> Result<(), Box<dyn std::error::Error>> {
+        fs::create_dir_all(directory)?;
+
+        let stem = cache_file_stem(asset.asset_id, &asset.variant);
+        fs::write(directory.join(format!("{}.bin", stem)), &asset.bytes)?;
+        fs::write(directory.join(format!("{}.json", stem)), serde_json::to_string(asset)?)?;
+
+        Ok(())
+    }
+
+    fn directory_invalidate(&self, directory: &Path, asset_id: u64) -> Result<(), Box<dyn std::error::Error>> {
+        if !directory.exists() {
+            return Ok(())
+        }
+
+        let prefix = format!("{}_", asset_id);
+        for entry in fs::read_dir(directory)? {
+            let entry = entry?;
+            if entry.file_name().to_string_lossy().starts_with(&prefix) {
+                fs::remove_file(entry.path())?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Backend {
+    pub fn set_asset_cache(&mut self, config: Option<AssetCacheConfig>) {
+        self.asset_cache = config.map(AssetCache::new);
+    }
+
+    fn asset_cache_bucket(&self, bucket_name: &Option<String>) -> GridFsBucket {
+        let options = GridFsBucketOptions::builder()
+            .bucket_name(bucket_name.clone().unwrap_or(DEFAULT_GRIDFS_BUCKET.to_string()))
+            .build();
+
+        self.get_database().gridfs_bucket(options)
+    }
+
+    pub(super) async fn asset_cache_get(&self, asset_id: u64, version: u64) -> Result<Option<Vec<u8>>, Box<dyn std::error::Error>> {
+        self.asset_cache_get_variant(asset_id, &version.to_string()).await
+    }
+
+    pub(super) async fn asset_cache_put(&self, asset_id: u64, version: u64, bytes: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+        self.asset_cache_put_variant(asset_id, &version.to_string(), bytes).await
+    }
+
+    pub(super) async fn asset_cache_get_variant(&self, asset_id: u64, variant: &str) -> Result<Option<Vec<u8>>, Box<dyn std::error::Error>> {
+        let cache = match &self.asset_cache {
+            Some(cache) => cache,
+            None => return Ok(None)
+        };
+
+        let asset = match &cache.config.storage {
+            AssetCacheStorage::Memory { .. } => cache.memory_get((asset_id, variant.to_string())),
+            AssetCacheStorage::Directory(directory) => cache.directory_get(directory, (asset_id, variant.to_string()))?,
+            AssetCacheStorage::GridFs { bucket_name } => {
+                let bucket = self.asset_cache_bucket(bucket_name);
+                let mut cursor = bucket.find(doc! { "filename": cache_file_stem(asset_id, variant) }, None).await?;
+
+                let mut found: Option<CachedAsset> = None;
+                while let Some(Ok(file)) = cursor.next().await {
+                    if found.is_some() {
+                        bucket.delete(file.id).await?;
+                        continue
+                    }
+
+                    let metadata = file.metadata.clone().unwrap_or_default();
+                    let mut asset = CachedAsset {
+                        asset_id,
+                        variant: variant.to_string(),
+                        sha256: metadata.get_str("sha256").unwrap_or_default().to_string(),
+                        size: file.length,
+                        cached_at: metadata.get_i64("cachedAt").unwrap_or_default() as u64,
+                        bytes: Vec::new()
+                    };
+
+                    let mut stream = bucket.open_download_stream(file.id.clone()).await?;
+                    stream.read_to_end(&mut asset.bytes).await?;
+
+                    if cache.is_valid(&asset) {
+                        found = Some(asset);
+                    } else {
+                        bucket.delete(file.id).await?;
+                    }
+                }
+
+                found
+            }
+        };
+
+        Ok(asset.map(|asset| asset.bytes))
+    }
+
+    pub(super) async fn asset_cache_put_variant(&self, asset_id: u64, variant: &str, bytes: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+        let cache = match &self.asset_cache {
+            Some(cache) => cache,
+            None => return Ok(())
+        };
+        if bytes.len() > cache.config.max_entry_size {
+            return Ok(())
+        }
+
+        let asset = CachedAsset {
+            asset_id,
+            variant: variant.to_string(),
+            sha256: sha256_hex(bytes),
+            size: bytes.len() as u64,
+            cached_at: datetime_now(),
+            bytes: bytes.to_vec()
+        };
+
+        match &cache.config.storage {
+            AssetCacheStorage::Memory { max_entries } => cache.memory_put(*max_entries, asset),
+            AssetCacheStorage::Directory(directory) => cache.directory_put(directory, &asset)?,
+            AssetCacheStorage::GridFs { bucket_name } => {
+                let bucket = self.asset_cache_bucket(bucket_name);
+                let filename = cache_file_stem(asset_id, variant);
+
+                let mut cursor = bucket.find(doc! { "filename": filename.clone() }, None).await?;
+                while let Some(Ok(file)) = cursor.next().await {
+                    bucket.delete(file.id).await?;
+                }
+
+                let options = GridFsUploadOptions::builder()
+                    .metadata(doc! {
+                        "assetId": asset_id as i64,
+                        "variant": variant,
+                        "sha256": asset.sha256.clone(),
+                        "cachedAt": asset.cached_at as i64
+                    })
+                    .build();
+                bucket.upload_from_futures_0_3_reader(filename, Cursor::new(asset.bytes), options).await?;
+            }
+        };
+
+        Ok(())
+    }
+
+    // Drops every cached version of the asset, thumbnails included
+    pub async fn invalidate_cached_asset(&self, asset_id: u64) -> Result<(), Box<dyn std::error::Error>> {
+        let cache = match &self.asset_cache {
+            Some(cache) => cache,
+            None => return Ok(())
+        };
+
+        match &cache.config.storage {
+            AssetCacheStorage::Memory { .. } => {
+                cache.memory.lock().unwrap().entries.retain(|key, _| key.0 != asset_id);
+            },
+            AssetCacheStorage::Directory(directory) => cache.directory_invalidate(directory, asset_id)?,
+            AssetCacheStorage::GridFs { bucket_name } => {
+                let bucket = self.asset_cache_bucket(bucket_name);
+
+                let mut cursor = bucket.find(doc! { "metadata.assetId": asset_id as i64 }, None).await?;
+                while let Some(Ok(file)) = cursor.next().await {
+                    bucket.delete(file.id).await?;
+                }
+            }
+        };
+
+        Ok(())
+    }
+}
@@ -0,0 +1,86 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use crate::Backend;
+use super::structs::{AssetType, Creator, ItemDetails, ThumbnailState};
+use super::thumbnails::ThumbnailFormat;
+
+const METADATA_THUMBNAIL_SIZE: &str = "420x420";
+const METADATA_THUMBNAIL_FORMAT: ThumbnailFormat = ThumbnailFormat::Png;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SalesStatus {
+    #[serde(rename = "isForSale")]
+    pub is_for_sale: bool,
+    #[serde(rename = "isPublicDomain")]
+    pub is_public_domain: bool,
+    #[serde(rename = "priceInRobux")]
+    pub price_in_robux: Option<u64>,
+    pub sales: Option<u64>,
+    #[serde(rename = "isLimited")]
+    pub is_limited: bool,
+    pub remaining: Option<u64>
+}
+
+// Everything a creator needs to see about an asset before whitelisting it
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AssetMetadata {
+    #[serde(rename = "assetId")]
+    pub asset_id: u64,
+    pub name: String,
+    pub description: String,
+    #[serde(rename = "assetType")]
+    pub asset_type: Option<AssetType>,
+    pub creator: Creator,
+    pub created: Option<DateTime<Utc>>,
+    pub updated: Option<DateTime<Utc>>,
+    pub sales: SalesStatus,
+    // None while Roblox is still rendering the thumbnail, or when it is moderated
+    #[serde(rename = "thumbnailUrl")]
+    pub thumbnail_url: Option<String>
+}
+
+impl From<ItemDetails> for AssetMetadata {
+    fn from(details: ItemDetails) -> Self {
+        Self {
+            asset_id: details.id as u64,
+            name: details.name,
+            description: details.description,
+            asset_type: details.asset_type_id,
+            creator: details.creator,
+            created: details.created,
+            updated: details.updated,
+            sales: SalesStatus {
+                is_for_sale: details.is_for_sale.unwrap_or(false),
+                is_public_domain: details.is_public_domain.unwrap_or(false),
+                price_in_robux: details.price_in_robux,
+                sales: details.sales,
+                is_limited: details.is_limited.unwrap_or(false) || details.is_limited_unique.unwrap_or(false),
+                remaining: details.remaining
+            },
+            thumbnail_url: None
+        }
+    }
+}
+
+impl Backend {
+    pub async fn get_asset_details(&self, asset_id: u64) -> Result<ItemDetails, Box<dyn std::error::Error>> {
+        self.fetch_asset_details_internal(asset_id).await
+    }
+
+    pub async fn user_owns_asset(&self, user_id: u64, asset_id: u64) -> Result<bool, Box<dyn std::error::Error>> {
+        self.user_own_asset_internal(user_id, asset_id).await
+    }
+
+    pub async fn get_asset_metadata(&self, asset_id: u64) -> Result<AssetMetadata, Box<dyn std::error::Error>> {
+        let mut metadata = AssetMetadata::from(self.fetch_asset_details_internal(asset_id).await?);
+
+        // The details are still useful without a thumbnail, so failing to get one isn't an error
+        if let Ok(thumbnail) = self.get_asset_thumbnail(asset_id, METADATA_THUMBNAIL_SIZE, METADATA_THUMBNAIL_FORMAT, false).await {
+            if thumbnail.state == ThumbnailState::Completed {
+                metadata.thumbnail_url = thumbnail.image_url;
+            }
+        }
+
+        Ok(metadata)
+    }
+}
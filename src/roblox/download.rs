@@ -0,0 +1,111 @@
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use bytes::Bytes;
+use futures::io::AsyncRead;
+use futures::stream::{Stream, TryStreamExt};
+use crate::Backend;
+
+pub(crate) const DEFAULT_MAX_ASSET_DOWNLOAD_SIZE: u64 = 100 * 1024 * 1024;
+
+// Yields the asset body chunk by chunk, already decompressed when the CDN sent it gzipped.
+// Errors out (and stops) as soon as more bytes than allowed have been received.
+pub struct AssetDownloadStream {
+    inner: Pin<Box<dyn Stream<Item = reqwest::Result<Bytes>> + Send>>,
+    // Not known for compressed responses
+    pub content_length: Option<u64>,
+    max_size: Option<u64>,
+    received: u64,
+    finished: bool
+}
+
+fn too_large_error(max_size: u64) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("Asset is larger than the maximum download size of {} bytes.", max_size))
+}
+
+impl AssetDownloadStream {
+    pub(crate) fn new(response: reqwest::Response, max_size: Option<u64>) -> Result<Self, Box<dyn std::error::Error>> {
+        let content_length = response.content_length();
+        // No point in downloading anything when the CDN already says it's too big
+        if let (Some(content_length), Some(max_size)) = (content_length, max_size) {
+            if content_length > max_size {
+                return Err(Box::new(too_large_error(max_size)))
+            }
+        }
+
+        Ok(Self {
+            inner: Box::pin(response.bytes_stream()),
+            content_length,
+            max_size,
+            received: 0,
+            finished: false
+        })
+    }
+
+    pub fn received(&self) -> u64 {
+        self.received
+    }
+
+    pub fn into_async_read(self) -> impl AsyncRead + Send + Unpin {
+        TryStreamExt::into_async_read(self)
+    }
+
+    pub async fn read_to_end(mut self) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let mut bytes: Vec<u8> = Vec::with_capacity(self.content_length.unwrap_or(0) as usize);
+        while let Some(chunk) = self.try_next().await? {
+            bytes.extend_from_slice(&chunk);
+        }
+
+        Ok(bytes)
+    }
+}
+
+impl Stream for AssetDownloadStream {
+    type Item = io::Result<Bytes>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if self.finished {
+            return Poll::Ready(None)
+        }
+
+        let chunk = match self.inner.as_mut().poll_next(cx) {
+            Poll::Pending => return Poll::Pending,
+            Poll::Ready(None) => {
+                self.finished = true;
+                return Poll::Ready(None)
+            },
+            Poll::Ready(Some(Err(error))) => {
+                self.finished = true;
+                return Poll::Ready(Some(Err(io::Error::other(error))))
+            },
+            Poll::Ready(Some(Ok(chunk))) => chunk
+        };
+
+        self.received += chunk.len() as u64;
+        if let Some(max_size) = self.max_size {
+            if self.received > max_size {
+                self.finished = true;
+                return Poll::Ready(Some(Err(too_large_error(max_size))))
+            }
+        }
+
+        Poll::Ready(Some(Ok(chunk)))
+    }
+}
+
+impl Backend {
+    // None removes the limit, which is only a good idea for trusted assets
+    pub fn set_max_asset_download_size(&mut self, max_size: Option<u64>) {
+        self.max_asset_download_size = max_size;
+    }
+
+    // Skips the asset cache, the caller gets the body as it arrives
+    pub async fn download_asset_stream(&self, asset_id: u64, version: Option<u64>) -> Result<AssetDownloadStream, Box<dyn std::error::Error>> {
+        let response = self.open_asset_download_internal(asset_id, version).await?;
+        AssetDownloadStream::new(response, self.max_asset_download_size)
+    }
+
+    pub async fn download_asset_reader(&self, asset_id: u64, version: Option<u64>) -> Result<impl AsyncRead + Send + Unpin, Box<dyn std::error::Error>> {
+        Ok(self.download_asset_stream(asset_id, version).await?.into_async_read())
+    }
+}
@@ -0,0 +1,45 @@
+use serde::{Deserialize, Serialize};
+use crate::Backend;
+
+// Who may whitelist models created by a group. A member qualifies by rank or by holding one of the listed roles.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GroupWhitelistPolicy {
+    #[serde(rename = "minRank")]
+    pub min_rank: u8,
+    #[serde(rename = "allowedRoleIds")]
+    pub allowed_role_ids: Vec<u64>
+}
+
+impl Default for GroupWhitelistPolicy {
+    fn default() -> Self {
+        // 255 is the group owner
+        Self { min_rank: 255, allowed_role_ids: Vec::new() }
+    }
+}
+
+impl Backend {
+    pub fn set_group_whitelist_policy(&mut self, policy: GroupWhitelistPolicy) {
+        self.group_whitelist_policy = policy;
+    }
+
+    // Rank of the user in the group, None if they aren't a member
+    pub async fn get_user_group_rank(&self, user_id: u64, group_id: u64) -> Result<Option<u8>, Box<dyn std::error::Error>> {
+        let roles = self.fetch_user_group_roles_internal(user_id).await?;
+
+        Ok(roles
+            .into_iter()
+            .find(|membership| membership.group.id == group_id)
+            .map(|membership| membership.role.rank))
+    }
+
+    pub(super) async fn user_can_whitelist_group_asset(&self, user_id: u64, group_id: u64) -> Result<bool, Box<dyn std::error::Error>> {
+        let roles = self.fetch_user_group_roles_internal(user_id).await?;
+        let policy = &self.group_whitelist_policy;
+
+        Ok(roles
+            .into_iter()
+            .find(|membership| membership.group.id == group_id)
+            .map(|membership| membership.role.rank >= policy.min_rank || policy.allowed_role_ids.contains(&membership.role.id))
+            .unwrap_or(false))
+    }
+}
@@ -0,0 +1,67 @@
+use std::time::Duration;
+use reqwest::{redirect, Client, Proxy};
+use crate::Backend;
+
+// Redirects from assetdelivery are followed by hand, the asset version is only sent along with the 302
+const MANUAL_REDIRECT_HOST: &str = "assetdelivery.roblox.com";
+
+#[derive(Debug, Clone)]
+pub struct HttpConfig {
+    pub timeout: Duration,
+    pub connect_timeout: Duration,
+    pub user_agent: String,
+    // e.g. "http://127.0.0.1:8080" or "socks5://...", used for every Roblox request
+    pub proxy: Option<String>,
+    pub max_redirects: usize
+}
+
+impl Default for HttpConfig {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(30),
+            connect_timeout: Duration::from_secs(10),
+            user_agent: format!("{}/{}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION")),
+            proxy: None,
+            max_redirects: 5
+        }
+    }
+}
+
+pub(crate) fn build_http_client(config: &HttpConfig) -> Result<Client, Box<dyn std::error::Error>> {
+    let max_redirects = config.max_redirects;
+    let redirect_policy = redirect::Policy::custom(move |attempt| {
+        let from_manual_host = attempt
+            .previous()
+            .last()
+            .and_then(|url| url.host_str())
+            .map(|host| host == MANUAL_REDIRECT_HOST)
+            .unwrap_or(false);
+
+        if from_manual_host {
+            attempt.stop()
+        } else if attempt.previous().len() > max_redirects {
+            attempt.error("too many redirects")
+        } else {
+            attempt.follow()
+        }
+    });
+
+    let mut builder = Client::builder()
+        .timeout(config.timeout)
+        .connect_timeout(config.connect_timeout)
+        .user_agent(config.user_agent.clone())
+        .redirect(redirect_policy);
+
+    if let Some(proxy) = &config.proxy {
+        builder = builder.proxy(Proxy::all(proxy)?);
+    }
+
+    Ok(builder.build()?)
+}
+
+impl Backend {
+    pub fn set_http_config(&mut self, config: HttpConfig) -> Result<(), Box<dyn std::error::Error>> {
+        self.http_client = build_http_client(&config)?;
+        Ok(())
+    }
+}
@@ -1,132 +1,363 @@
 use crate::Backend;  
 
-mod structs;
+pub(crate) mod structs;
 mod rbxm;
+mod sanitize;
+mod batch;
+mod versions;
+mod session;
+pub(crate) mod groups;
+mod details;
+mod thumbnails;
+pub(crate) mod accounts;
+pub(crate) mod cache;
+pub(crate) mod download;
+pub(crate) mod http;
+pub(crate) mod scheduler;
+pub(crate) mod users;
+
+pub use accounts::{AccountRoutingStrategy, AccountStatus};
+pub use cache::{AssetCacheConfig, AssetCacheStorage, CachedAsset};
+pub use groups::GroupWhitelistPolicy;
+pub use http::HttpConfig;
+pub use rbxm::AssetReference;
+pub use session::RobloxSessionStatus;
+pub use details::{AssetMetadata, SalesStatus};
+pub use download::AssetDownloadStream;
+pub use structs::{AssetType, CatalogItemDetails, Creator, CreatorType, ItemDetails, RobloxApiError, RobloxError, RobloxUser, Thumbnail, ThumbnailState};
+pub use thumbnails::{AssetThumbnail, ThumbnailFormat};
+pub use scheduler::{RobloxEndpoint, SchedulerConfig, SchedulerStats};
+pub use sanitize::{SanitizeAction, SanitizeChange, SanitizePolicy, SanitizedModel};
+pub use versions::{ScriptChange, ScriptChangeKind};
 
 impl Backend {
+    // Only Models can be whitelisted unless configured otherwise
+    pub fn set_whitelistable_asset_types(&mut self, asset_types: Vec<AssetType>) {
+        self.whitelistable_asset_types = asset_types;
+    }
+
     pub async fn whitelist_asset(&self, asset_id: u64, user_id_requesting: u64) -> Result<(), Box<dyn std::error::Error>> {
-        if !self.user_own_asset_internal(user_id_requesting, asset_id).await? {
-            return Err("User does not own asset.".into())
-        }
         let item_details = self.fetch_asset_details_internal(asset_id).await?;
+        match item_details.creator.creator_type {
+            structs::CreatorType::User => {
+                if !self.user_own_asset_internal(user_id_requesting, asset_id).await? {
+                    return Err("User does not own asset.".into())
+                }
+            },
+            structs::CreatorType::Group => {
+                if !self.user_can_whitelist_group_asset(user_id_requesting, item_details.creator.target_id as u64).await? {
+                    return Err("User does not have a high enough role in the group that owns the asset.".into())
+                }
+            }
+        }
+
         if item_details.is_public_domain.is_none() || !item_details.is_public_domain.unwrap() {
             return Err("Asset is not for sale.".into())
-        } else if item_details.asset_type_id.is_none() || item_details.asset_type_id.unwrap() != structs::AssetType::Model {
-            return Err("Asset type is not a Model.".into())
+        } else if item_details.asset_type_id.is_none() || !self.whitelistable_asset_types.contains(&item_details.asset_type_id.unwrap()) {
+            return Err("Asset type cannot be whitelisted.".into())
         } else if item_details.price_in_robux.is_some() && item_details.price_in_robux.unwrap() > 0 {
             return Err("Asset costs robux.".into())
         }
 
-        self.purchase_asset_internal(asset_id).await?;
+        // The same account is picked for the asset when routing is sticky, so it's also the one downloading it later
+        let account = self.accounts.select(Some(asset_id))?;
+        let bot_user_id = self.account_user_id(account).await?;
+        if !self.user_own_asset_internal(bot_user_id, asset_id).await? {
+            self.purchase_asset_internal(account, asset_id).await?;
+        }
+
+        // Pin the version that was reviewed, so later updates to the asset aren't approved implicitly
+        if self.mongo_client.is_some() {
+            let version = self.fetch_asset_version_internal(asset_id).await?;
+            self.record_whitelisted_asset(asset_id, version, user_id_requesting, Some(bot_user_id)).await?;
+        }
         Ok(())
     }
 
+    // Downloads the current version, which is only cached under its version number so updates are never hidden
     pub async fn download_asset_bytes(&self, asset_id: u64) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
-        self.download_asset_internal(asset_id).await
+        match self.fetch_asset_version_internal(asset_id).await? {
+            Some(version) => self.download_asset_version_bytes(asset_id, version).await,
+            None => self.download_asset_internal(asset_id, None).await
+        }
     }
 }
 
 mod internal {
-    use reqwest::{header, Client};
-    use surf::StatusCode;
-    use crate::{utils, Backend}; 
-    use super::structs::{AssetDeliveryResponse, AssetPurchaseReq, ItemDetails, RobloxApiError};
+    use reqwest::{header, StatusCode};
+    use crate::Backend;
+    use super::accounts::{RobloxAccount, XCSRF_HEADER};
+    use super::download::AssetDownloadStream;
+    use super::scheduler::{RetryPolicy, RobloxEndpoint};
+    use super::structs::{AssetPurchaseReq, AuthenticatedUser, CatalogItemDetails, CatalogItemReq, CatalogItemsDetailsReq, CatalogItemsDetailsResponse, ItemDetails, RobloxApiError, RobloxUser, Thumbnail, ThumbnailsResponse, UserGroupRole, UserGroupRolesResponse, UserIdLookupResult, UserIdsLookupReq, UserIdsLookupResponse, UsernameLookupResult, UsernamesLookupReq, UsernamesLookupResponse};
 
-    const AUTH_URL: &str = "https://auth.roblox.com";
     const ASSETDELIVERY_URL: &str = "https://assetdelivery.roblox.com/v1";
+    const CATALOG_URL: &str = "https://catalog.roblox.com/v1";
     const ECONOMY_V1_URL: &str = "https://economy.roblox.com/v1";
     const ECONOMY_V2_URL: &str = "https://economy.roblox.com/v2";
     const INVENTORY_URL: &str = "https://inventory.roblox.com/v1";
-    const XCSRF_HEADER: &str = "x-csrf-token";
+    const USERS_URL: &str = "https://users.roblox.com/v1";
+    const GROUPS_URL: &str = "https://groups.roblox.com/v1";
+    const THUMBNAILS_URL: &str = "https://thumbnails.roblox.com/v1";
+    const ASSET_VERSION_HEADER: &str = "roblox-assetversionnumber";
+
+    pub(super) async fn error_from_response(response: reqwest::Response) -> Box<dyn std::error::Error> {
+        let status_code = response.status();
+        match response.json::<RobloxApiError>().await {
+            Ok(info) => {
+                match info.errors.first() {
+                    Some(err) => format!("Roblox returned error code: {}, message: {}", status_code, err.message.clone()).into(),
+                    None => format!("Roblox returned error code: {}", status_code).into()
+                }
+            },
+            Err(_) => format!("Roblox returned error code: {}", status_code).into()
+        }
+    }
+
+    fn asset_delivery_url(asset_id: u64, version: Option<u64>) -> String {
+        match version {
+            Some(version) => format!("{}/asset?id={}&version={}", ASSETDELIVERY_URL, asset_id, version),
+            None => format!("{}/asset?id={}", ASSETDELIVERY_URL, asset_id)
+        }
+    }
 
     impl Backend {
-        pub(super) fn prepare_headers(&self) -> header::HeaderMap {
-            let mut reqwest_headers = header::HeaderMap::new();
-    
-            // send help
-            let xcsrf_header = header::HeaderValue::from_static(utils::string_to_static_str(self.roblox_xcsrf_token.to_owned()));
-            reqwest_headers.insert(XCSRF_HEADER, xcsrf_header);
-            let mut cookie_header = header::HeaderValue::from_static(utils::string_to_static_str(format!(".ROBLOSECURITY={}", self.roblox_cookie.to_owned())));
-            cookie_header.set_sensitive(true);
-            reqwest_headers.insert(header::COOKIE, cookie_header);
-    
-            reqwest_headers
-        }
-    
-        pub(crate) async fn refresh_xcsrf_token(&mut self) -> Result<(), Box<dyn std::error::Error>> {
-            let request_result = Client::new()
-                .post(AUTH_URL)
-                .headers(self.prepare_headers())
-                .send()
-                .await?;
-    
-            let xcsrf = request_result
-                .headers()
-                .get(XCSRF_HEADER)
-                .map(|x| x.to_str().unwrap().to_string())
-                .unwrap();
-    
-            self.roblox_xcsrf_token = xcsrf;
-            Ok(())
+        // Sends the request as one of the pool's accounts, see `send_as_account`
+        pub(super) async fn send_authenticated<F>(&self, endpoint: RobloxEndpoint, asset_id: Option<u64>, build_request: F) -> Result<reqwest::Response, Box<dyn std::error::Error>>
+        where
+            F: Fn(&reqwest::Client) -> reqwest::RequestBuilder
+        {
+            let account = self.accounts.select(asset_id)?;
+            let response = self.send_as_account(account, endpoint, build_request).await?;
+
+            if response.status() == StatusCode::UNAUTHORIZED {
+                return Err(error_from_response(response).await)
+            }
+            Ok(response)
         }
 
-        pub(super) async fn download_asset_internal(&self, asset_id: u64) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
-            let formatted_url = format!(
-                "{}/asset?id={}",
-                ASSETDELIVERY_URL,
-                asset_id
-            );
+        pub(super) async fn send_as_account<F>(&self, account: &RobloxAccount, endpoint: RobloxEndpoint, build_request: F) -> Result<reqwest::Response, Box<dyn std::error::Error>>
+        where
+            F: Fn(&reqwest::Client) -> reqwest::RequestBuilder
+        {
+            self.send_as_account_with_policy(account, endpoint, RetryPolicy::Idempotent, build_request).await
+        }
 
-            let mut cdn_redirect_response = surf::get(formatted_url)
-                .header(XCSRF_HEADER, self.roblox_xcsrf_token.clone())
-                .header("Cookie", format!(".ROBLOSECURITY={}", self.roblox_cookie.clone()))
-                .send()
-                .await?;
-
-            if cdn_redirect_response.status() != 302 {
-                let status_code = cdn_redirect_response.status();
-                return match cdn_redirect_response.body_json::<RobloxApiError>().await {
-                    Ok(info) => {
-                        match info.errors.first() {
-                            Some(err) => Err(format!("Roblox returned error code: {}, message: {}", status_code, err.message.clone()).into()),
-                            None => Err(format!("Roblox returned error code: {}", status_code).into())
+        // Picks up a new x-csrf-token whenever Roblox asks for one, and takes the account out of rotation on a 401
+        pub(super) async fn send_as_account_with_policy<F>(&self, account: &RobloxAccount, endpoint: RobloxEndpoint, policy: RetryPolicy, build_request: F) -> Result<reqwest::Response, Box<dyn std::error::Error>>
+        where
+            F: Fn(&reqwest::Client) -> reqwest::RequestBuilder
+        {
+            loop {
+                let response = self.scheduler.execute_with_policy(endpoint, policy, || async {
+                    Ok(build_request(&self.http_client)
+                        .headers(account.headers()?)
+                        .send()
+                        .await?)
+                }).await?;
+
+                match response.status() {
+                    StatusCode::UNAUTHORIZED => {
+                        account.mark_unhealthy("Roblox rejected the account cookie.");
+                        return Ok(response)
+                    },
+                    StatusCode::FORBIDDEN => {
+                        let new_token = response
+                            .headers()
+                            .get(XCSRF_HEADER)
+                            .and_then(|token| token.to_str().ok())
+                            .map(|token| token.to_string());
+
+                        match new_token {
+                            Some(token) if account.update_xcsrf_token(&token) => continue,
+                            _ => return Ok(response)
                         }
                     },
-                    Err(_) => Err(format!("Roblox returned error code: {}", status_code).into())
+                    _ => return Ok(response)
                 }
             }
+        }
 
-            let location = match cdn_redirect_response.header("Location") {
-                Some(location) => location.as_str(),
-                None => return Err("Roblox did not return location for asset.".into())
+        pub(super) async fn fetch_user_group_roles_internal(&self, user_id: u64) -> Result<Vec<UserGroupRole>, Box<dyn std::error::Error>> {
+            let formatted_url = format!(
+                "{}/users/{}/groups/roles",
+                GROUPS_URL,
+                user_id
+            );
+
+            let request_result = self.send_authenticated(RobloxEndpoint::Groups, None, |client| client.get(&formatted_url)).await?;
+            if request_result.status() != StatusCode::OK {
+                return Err(error_from_response(request_result).await)
+            }
+
+            Ok(request_result.json::<UserGroupRolesResponse>().await?.data)
+        }
+
+        // `size` and `format` are passed through as-is, e.g. "420x420" and "Png"
+        pub(super) async fn fetch_asset_thumbnails_internal(&self, asset_ids: &[u64], size: &str, format: &str) -> Result<Vec<Thumbnail>, Box<dyn std::error::Error>> {
+            let joined_ids = asset_ids
+                .iter()
+                .map(|asset_id| asset_id.to_string())
+                .collect::<Vec<String>>()
+                .join(",");
+            let formatted_url = format!(
+                "{}/assets?assetIds={}&size={}&format={}",
+                THUMBNAILS_URL,
+                joined_ids,
+                size,
+                format
+            );
+
+            let request_result = self.scheduler.execute(RobloxEndpoint::Thumbnails, || async {
+                Ok(self.http_client
+                    .get(&formatted_url)
+                    .send()
+                    .await?)
+            }).await?;
+            if request_result.status() != StatusCode::OK {
+                return Err(error_from_response(request_result).await)
+            }
+
+            Ok(request_result.json::<ThumbnailsResponse>().await?.data)
+        }
+
+        // None when no user has that ID
+        pub(super) async fn fetch_user_internal(&self, user_id: u64) -> Result<Option<RobloxUser>, Box<dyn std::error::Error>> {
+            let formatted_url = format!(
+                "{}/users/{}",
+                USERS_URL,
+                user_id
+            );
+
+            let request_result = self.scheduler.execute(RobloxEndpoint::Users, || async {
+                Ok(self.http_client
+                    .get(&formatted_url)
+                    .send()
+                    .await?)
+            }).await?;
+            match request_result.status() {
+                StatusCode::NOT_FOUND => Ok(None),
+                StatusCode::OK => Ok(Some(request_result.json::<RobloxUser>().await?)),
+                _ => Err(error_from_response(request_result).await)
+            }
+        }
+
+        // Only has names, users that don't exist are left out of the result
+        pub(super) async fn fetch_users_by_ids_internal(&self, user_ids: &[u64]) -> Result<Vec<UserIdLookupResult>, Box<dyn std::error::Error>> {
+            let formatted_url = format!(
+                "{}/users",
+                USERS_URL
+            );
+
+            let request_body = UserIdsLookupReq {
+                user_ids: user_ids.to_vec(),
+                exclude_banned_users: false
+            };
+
+            let request_result = self.scheduler.execute(RobloxEndpoint::Users, || async {
+                Ok(self.http_client
+                    .post(&formatted_url)
+                    .json(&request_body)
+                    .send()
+                    .await?)
+            }).await?;
+            if request_result.status() != StatusCode::OK {
+                return Err(error_from_response(request_result).await)
+            }
+
+            Ok(request_result.json::<UserIdsLookupResponse>().await?.data)
+        }
+
+        // Usernames that don't exist are left out of the result
+        pub(super) async fn fetch_users_by_usernames_internal(&self, usernames: &[String]) -> Result<Vec<UsernameLookupResult>, Box<dyn std::error::Error>> {
+            let formatted_url = format!(
+                "{}/usernames/users",
+                USERS_URL
+            );
+
+            let request_body = UsernamesLookupReq {
+                usernames: usernames.to_vec(),
+                exclude_banned_users: false
             };
 
-            let mut request_result = surf::get(location)
-                .header(XCSRF_HEADER, self.roblox_xcsrf_token.clone())
-                .header("Cookie", format!(".ROBLOSECURITY={}", self.roblox_cookie.clone()))
-                .send()
-                .await?;
+            let request_result = self.scheduler.execute(RobloxEndpoint::Users, || async {
+                Ok(self.http_client
+                    .post(&formatted_url)
+                    .json(&request_body)
+                    .send()
+                    .await?)
+            }).await?;
+            if request_result.status() != StatusCode::OK {
+                return Err(error_from_response(request_result).await)
+            }
+
+            Ok(request_result.json::<UsernamesLookupResponse>().await?.data)
+        }
+
+        // None when the account's cookie is no longer valid
+        pub(super) async fn fetch_authenticated_user_internal(&self, account: &RobloxAccount) -> Result<Option<AuthenticatedUser>, Box<dyn std::error::Error>> {
+            let formatted_url = format!(
+                "{}/users/authenticated",
+                USERS_URL
+            );
 
+            let request_result = self.send_as_account(account, RobloxEndpoint::Auth, |client| client.get(&formatted_url)).await?;
             match request_result.status() {
-                StatusCode::Ok => {},
-                _ => {
-                    let status_code = request_result.status();
-                    return match request_result.body_json::<RobloxApiError>().await {
-                        Ok(info) => {
-                            match info.errors.first() {
-                                Some(err) => Err(format!("Roblox returned error code: {}, message: {}", status_code, err.message.clone()).into()),
-                                None => Err(format!("Roblox returned error code: {}", status_code).into())
-                            }
-                        },
-                        Err(_) => Err(format!("Roblox returned error code: {}", status_code).into())
-                    }
-                }
+                StatusCode::UNAUTHORIZED => Ok(None),
+                StatusCode::OK => Ok(Some(request_result.json::<AuthenticatedUser>().await?)),
+                _ => Err(error_from_response(request_result).await)
+            }
+        }
+
+        // Asks assetdelivery for the asset without following the CDN redirect, the version is only in the headers
+        pub(super) async fn fetch_asset_version_internal(&self, asset_id: u64) -> Result<Option<u64>, Box<dyn std::error::Error>> {
+            let cdn_redirect_response = self.send_authenticated(RobloxEndpoint::AssetDelivery, Some(asset_id), |client| client.get(asset_delivery_url(asset_id, None))).await?;
+
+            if cdn_redirect_response.status() != StatusCode::FOUND {
+                return Err(error_from_response(cdn_redirect_response).await)
+            }
+
+            Ok(cdn_redirect_response
+                .headers()
+                .get(ASSET_VERSION_HEADER)
+                .and_then(|version| version.to_str().ok())
+                .and_then(|version| version.parse::<u64>().ok()))
+        }
+
+        // Returns the CDN response with the body left unread, so callers decide how to consume it
+        pub(super) async fn open_asset_download_internal(&self, asset_id: u64, version: Option<u64>) -> Result<reqwest::Response, Box<dyn std::error::Error>> {
+            let cdn_redirect_response = self.send_authenticated(RobloxEndpoint::AssetDelivery, Some(asset_id), |client| client.get(asset_delivery_url(asset_id, version))).await?;
+
+            if cdn_redirect_response.status() != StatusCode::FOUND {
+                return Err(error_from_response(cdn_redirect_response).await)
+            }
+
+            let location = match cdn_redirect_response.headers().get(header::LOCATION) {
+                Some(location) => location.to_str()?.to_string(),
+                None => return Err("Roblox did not return location for asset.".into())
             };
 
-            let bytes: Vec<u8> = request_result.body_bytes().await?;
-            Ok(bytes)
+            // The CDN doesn't need the account cookie, so it isn't sent along
+            let request_result = self.scheduler.execute(RobloxEndpoint::Cdn, || async {
+                Ok(self.http_client
+                    .get(&location)
+                    .send()
+                    .await?)
+            }).await?;
+
+            if request_result.status() != StatusCode::OK {
+                return Err(error_from_response(request_result).await)
+            }
+
+            Ok(request_result)
+        }
+
+        pub(super) async fn download_asset_internal(&self, asset_id: u64, version: Option<u64>) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+            let response = self.open_asset_download_internal(asset_id, version).await?;
+
+            AssetDownloadStream::new(response, self.max_asset_download_size)?.read_to_end().await
         }
-    
+
         pub(super) async fn user_own_asset_internal(&self, user_id: u64, asset_id: u64) -> Result<bool, Box<dyn std::error::Error>> {
             let formatted_url = format!(
                 "{}/users/{}/items/Asset/{}/is-owned",
@@ -134,55 +365,65 @@ mod internal {
                 user_id,
                 asset_id
             );
-    
-            let request_result = Client::new()
-                .get(formatted_url)
-                .headers(self.prepare_headers())
-                .send()
-                .await?;
-    
+
+            let request_result = self.send_authenticated(RobloxEndpoint::Inventory, Some(asset_id), |client| client.get(&formatted_url)).await?;
+
             match request_result.text().await.unwrap_or(String::new()).parse::<bool>() {
                 Ok(res) => Ok(res),
                 Err(_) => Ok(false)
             }
         }
-    
+
         pub(super) async fn fetch_asset_details_internal(&self, asset_id: u64) -> Result<ItemDetails, Box<dyn std::error::Error>> {
             let formatted_url = format!(
                 "{}/assets/{}/details",
                 ECONOMY_V2_URL,
                 asset_id
             );
-    
-            let request_result = Client::new()
-                .get(formatted_url)
-                .headers(self.prepare_headers())
-                .send()
-                .await?;
+
+            let request_result = self.send_authenticated(RobloxEndpoint::Economy, Some(asset_id), |client| client.get(&formatted_url)).await?;
 
             Ok(request_result.json::<ItemDetails>().await?)
         }
-    
-        pub(super) async fn purchase_asset_internal(&self, asset_id: u64) -> Result<(), Box<dyn std::error::Error>> {
+
+        // Assets that aren't in the catalog, e.g. models, are left out of the response
+        pub(super) async fn fetch_catalog_items_details_internal(&self, asset_ids: &[u64]) -> Result<Vec<CatalogItemDetails>, Box<dyn std::error::Error>> {
+            let formatted_url = format!(
+                "{}/catalog/items/details",
+                CATALOG_URL
+            );
+
+            let request_body = CatalogItemsDetailsReq {
+                items: asset_ids.iter().map(|asset_id| CatalogItemReq { item_type: "Asset".to_string(), id: *asset_id }).collect()
+            };
+
+            let request_result = self.send_authenticated(RobloxEndpoint::Catalog, None, |client| client.post(&formatted_url).json(&request_body)).await?;
+            if request_result.status() != StatusCode::OK {
+                return Err(error_from_response(request_result).await)
+            }
+
+            Ok(request_result.json::<CatalogItemsDetailsResponse>().await?.data)
+        }
+
+        pub(super) async fn purchase_asset_internal(&self, account: &RobloxAccount, asset_id: u64) -> Result<(), Box<dyn std::error::Error>> {
             let formatted_url = format!(
                 "{}/purchases/products/{}",
                 ECONOMY_V1_URL,
                 asset_id
             );
-    
+
             let request_body = AssetPurchaseReq {
                 expected_currency: 1,
                 expected_price: 0,
             };
-    
-            Client::new()
-                .post(formatted_url)
-                .headers(self.prepare_headers())
-                .json(&request_body)
-                .send()
-                .await?;
-    
+
+            // Retrying after a server error could buy the asset twice
+            let request_result = self.send_as_account_with_policy(account, RobloxEndpoint::Economy, RetryPolicy::ThrottledOnly, |client| client.post(&formatted_url).json(&request_body)).await?;
+            if request_result.status() != StatusCode::OK {
+                return Err(error_from_response(request_result).await)
+            }
+
             Ok(())
         }
     }
-}
\ No newline at end of file
+}
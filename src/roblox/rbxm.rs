@@ -1,20 +1,132 @@
 use std::collections::HashMap;
 use std::io::{Cursor, BufReader};
+use full_moon::ast::{Call, Expression, FunctionArgs, Suffix};
 use rbx_binary;
 use rbx_dom_weak::{WeakDom, Instance};
 use rbx_types::Variant;
+use serde::{Deserialize, Serialize};
 use crate::Backend;
 
-fn search_for_classnames<'a>(dom: &'a WeakDom, classnames: &Vec<&str>, instances: &mut HashMap<Vec<&'a str>, &'a Instance>, mut names: Vec<&'a str>, instance: &'a Instance) {
-    names.push(instance.name.as_str());
-    for &child_ref in instance.children() {
-        if let Some(instance) =  dom.get_by_ref(child_ref) {
-            if classnames.contains(&instance.class.as_str()) {
-                instances.insert(names.clone(), &instance);
+const ASSET_URL_PREFIXES: [&str; 2] = ["rbxassetid://", "roblox.com/asset"];
+pub(crate) const SCRIPT_CLASSNAMES: [&str; 3] = ["Script", "LocalScript", "ModuleScript"];
+
+// Where an asset ID was found in a model, e.g. `Workspace.Part.Mesh` / `MeshId`.
+// References coming from `require(id)` in scripts use `Source` as the property.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct AssetReference {
+    #[serde(rename = "assetId")]
+    pub asset_id: u64,
+    pub path: String,
+    pub property: String,
+    pub url: String
+}
+
+// Accepts `rbxassetid://123` and `http(s)://www.roblox.com/asset/?id=123` (and variations of it)
+pub(crate) fn parse_asset_id(url: &str) -> Option<u64> {
+    let url = url.trim().to_lowercase();
+
+    let id_part = if let Some(rest) = url.strip_prefix(ASSET_URL_PREFIXES[0]) {
+        rest
+    } else if url.contains(ASSET_URL_PREFIXES[1]) {
+        let query = url.split_once('?')?.1;
+        query
+            .split('&')
+            .find_map(|pair| pair.strip_prefix("id="))?
+    } else {
+        return None
+    };
+
+    let digits: String = id_part.chars().take_while(|c| c.is_ascii_digit()).collect();
+    digits.parse::<u64>().ok()
+}
+
+pub(crate) fn required_asset_ids(backend: &Backend, source: &str) -> Vec<u64> {
+    let ast = match backend.luau_ast_from_string(source) {
+        Ok(ast) => ast,
+        Err(_) => return Vec::new()
+    };
+
+    let mut ids: Vec<(usize, u64)> = Vec::new();
+    for (range, suffixes) in backend.luau_find_global_function_usage(&ast, "require") {
+        if let Some(Suffix::Call(Call::AnonymousCall(FunctionArgs::Parentheses { arguments, .. }))) = suffixes.first() {
+            if let Some(Expression::Number(token)) = arguments.iter().next() {
+                if let Ok(id) = token.token().to_string().parse::<u64>() {
+                    ids.push((range.0, id));
+                }
             }
+        }
+    }
+
+    // Keep the order they appear in the source
+    ids.sort();
+    ids.into_iter().map(|(_, id)| id).collect()
+}
+
+fn search_for_asset_references(backend: &Backend, dom: &WeakDom, references: &mut Vec<AssetReference>, mut names: Vec<String>, instance: &Instance) {
+    names.push(instance.name.clone());
+    let path = names.join(".");
+
+    let mut property_names: Vec<&String> = instance.properties.keys().collect();
+    property_names.sort();
+
+    for property in property_names {
+        match &instance.properties[property] {
+            Variant::Content(content) => {
+                let url: &str = content.as_ref();
+                if let Some(asset_id) = parse_asset_id(url) {
+                    references.push(AssetReference { asset_id, path: path.clone(), property: property.clone(), url: url.to_string() });
+                }
+            },
+            Variant::String(src) if property == "Source" => {
+                for asset_id in required_asset_ids(backend, src) {
+                    references.push(AssetReference { asset_id, path: path.clone(), property: property.clone(), url: format!("require({})", asset_id) });
+                }
+            },
+            _ => {}
+        }
+    }
+
+    for &child_ref in instance.children() {
+        if let Some(child) = dom.get_by_ref(child_ref) {
+            search_for_asset_references(backend, dom, references, names.clone(), child);
+        }
+    }
+}
+
+// Siblings sharing a name get "#2", "#3"... in child order, so every instance has a path of its own
+fn named_children<'a>(dom: &'a WeakDom, instance: &'a Instance) -> Vec<(String, &'a Instance)> {
+    let mut seen: HashMap<&str, usize> = HashMap::new();
+
+    instance
+        .children()
+        .iter()
+        .filter_map(|child_ref| dom.get_by_ref(*child_ref))
+        .map(|child| {
+            let count = seen.entry(child.name.as_str()).or_insert(0);
+            *count += 1;
+            let name = match *count {
+                1 => child.name.clone(),
+                count => format!("{}#{}", child.name, count)
+            };
+            (name, child)
+        })
+        .collect()
+}
+
+fn search_for_scripts(dom: &WeakDom, scripts: &mut HashMap<String, String>, path: &str, instance: &Instance) {
+    for (name, child) in named_children(dom, instance) {
+        let child_path = if path.is_empty() { name } else { format!("{}.{}", path, name) };
 
-            search_for_classnames(dom, classnames, instances, names.clone(), &instance);
+        if SCRIPT_CLASSNAMES.contains(&child.class.as_str()) {
+            // Scripts saved without a Source are empty
+            let source = match child.properties.get("Source") {
+                Some(Variant::String(source)) => source.clone(),
+                _ => String::new()
+            };
+            scripts.insert(child_path.clone(), source);
         }
+
+        search_for_scripts(dom, scripts, &child_path, child);
     }
 }
 
@@ -26,28 +138,30 @@ impl Backend {
         Ok(rbx_binary::from_reader(buf_reader)?)
     }
 
-    pub fn dom_find_scripts<'a>(&'a self, dom: &'a WeakDom) -> HashMap<String, String> {
+    pub fn dom_to_bytes(&self, dom: &WeakDom) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let mut bytes: Vec<u8> = Vec::new();
+        rbx_binary::to_writer(&mut bytes, dom, dom.root().children())?;
+
+        Ok(bytes)
+    }
+
+    // Keyed by the path of each script, e.g. `Model.Folder.Script`
+    pub fn dom_find_scripts(&self, dom: &WeakDom) -> HashMap<String, String> {
         let mut scripts: HashMap<String, String> = HashMap::new();
+        search_for_scripts(dom, &mut scripts, "", dom.root());
+
+        scripts
+    }
 
-        let classnames: Vec<&str> = vec!["Script", "LocalScript", "ModuleScript"];
-        let mut instances: HashMap<Vec<&str>, &Instance> = HashMap::new();
+    // Collects every asset referenced by Content properties (MeshId, TextureID, SoundId, ...) and `require(id)` calls
+    pub fn dom_find_asset_references(&self, dom: &WeakDom) -> Vec<AssetReference> {
+        let mut references: Vec<AssetReference> = Vec::new();
         for &instance_ref in dom.root().children() {
-            if let Some(instance) = &dom.get_by_ref(instance_ref) {
-                search_for_classnames(&dom, &classnames, &mut instances, Vec::new(), instance);
+            if let Some(instance) = dom.get_by_ref(instance_ref) {
+                search_for_asset_references(self, dom, &mut references, Vec::new(), instance);
             }
         }
 
-        for e in instances {
-            let source = e.1.properties.get("Source").unwrap();
-            match source {
-                Variant::String(src) => {
-                    let path = e.0.join(".");
-                    scripts.insert(path, src.to_string());
-                },
-                _ => {}
-            };
-        }
-
-        scripts
+        references
     }
 }
\ No newline at end of file
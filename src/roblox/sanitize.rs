@@ -0,0 +1,189 @@
+use rbx_dom_weak::{WeakDom, Instance};
+use rbx_types::{Content, Ref, Variant};
+use serde::{Deserialize, Serialize};
+use crate::Backend;
+use super::rbxm::{parse_asset_id, required_asset_ids, SCRIPT_CLASSNAMES};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct SanitizePolicy {
+    // Instances of these classes are removed along with their descendants
+    #[serde(rename = "forbiddenClasses")]
+    pub forbidden_classes: Vec<String>,
+    // Scripts referencing any of these globals are removed, called or not (e.g. `getfenv`, `loadstring`)
+    #[serde(rename = "bannedFunctions")]
+    pub banned_functions: Vec<String>,
+    // Properties removed from every instance, falling back to their defaults once serialized
+    #[serde(rename = "clearedProperties")]
+    pub cleared_properties: Vec<String>,
+    // None leaves asset URLs untouched, otherwise allowed assets are rewritten to `rbxassetid://` and others are cleared
+    #[serde(rename = "allowedAssetIds")]
+    pub allowed_asset_ids: Option<Vec<u64>>
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SanitizeAction {
+    RemovedInstance,
+    RemovedScript,
+    ClearedProperty,
+    RewroteAssetUrl,
+    RemovedAssetUrl
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SanitizeChange {
+    pub action: SanitizeAction,
+    pub path: String,
+    #[serde(rename = "className")]
+    pub class_name: String,
+    pub detail: String
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SanitizedModel {
+    pub bytes: Vec<u8>,
+    pub changes: Vec<SanitizeChange>
+}
+
+enum Mutation {
+    Destroy,
+    RemoveProperty(String),
+    SetProperty(String, Variant)
+}
+
+fn banned_function_in_script(backend: &Backend, source: &str, banned_functions: &[String]) -> Option<String> {
+    let ast = match backend.luau_ast_from_string(source) {
+        Ok(ast) => ast,
+        Err(_) => return Some("script could not be parsed".to_string())
+    };
+
+    banned_functions
+        .iter()
+        .find(|function| !backend.luau_find_global_references(&ast, function).is_empty())
+        .map(|function| format!("uses {}", function))
+}
+
+fn plan_for_instance(backend: &Backend, policy: &SanitizePolicy, instance: &Instance, path: &str, mutations: &mut Vec<(Ref, Mutation)>, changes: &mut Vec<SanitizeChange>) -> bool {
+    let mut change = |action: SanitizeAction, detail: String| changes.push(SanitizeChange {
+        action,
+        path: path.to_string(),
+        class_name: instance.class.clone(),
+        detail
+    });
+
+    if policy.forbidden_classes.contains(&instance.class) {
+        mutations.push((instance.referent(), Mutation::Destroy));
+        change(SanitizeAction::RemovedInstance, "forbidden class".to_string());
+        return false
+    }
+
+    if SCRIPT_CLASSNAMES.contains(&instance.class.as_str()) {
+        if let Some(Variant::String(source)) = instance.properties.get("Source") {
+            let mut reason = if policy.banned_functions.is_empty() {
+                None
+            } else {
+                banned_function_in_script(backend, source, &policy.banned_functions)
+            };
+            // Modules can't be rewritten like Content properties, so the whole script has to go
+            if let (None, Some(allowed_asset_ids)) = (&reason, &policy.allowed_asset_ids) {
+                reason = required_asset_ids(backend, source)
+                    .into_iter()
+                    .find(|id| !allowed_asset_ids.contains(id))
+                    .map(|id| format!("requires asset {} outside of the allowlist", id));
+            }
+
+            if let Some(reason) = reason {
+                mutations.push((instance.referent(), Mutation::Destroy));
+                change(SanitizeAction::RemovedScript, reason);
+                return false
+            }
+        }
+    }
+
+    let mut property_names: Vec<&String> = instance.properties.keys().collect();
+    property_names.sort();
+
+    for property in property_names {
+        if policy.cleared_properties.contains(property) {
+            mutations.push((instance.referent(), Mutation::RemoveProperty(property.clone())));
+            change(SanitizeAction::ClearedProperty, property.clone());
+            continue
+        }
+
+        let allowed_asset_ids = match &policy.allowed_asset_ids {
+            Some(ids) => ids,
+            None => continue
+        };
+        if let Variant::Content(content) = &instance.properties[property] {
+            let url: &str = content.as_ref();
+            let asset_id = match parse_asset_id(url) {
+                Some(id) => id,
+                None => continue
+            };
+
+            if allowed_asset_ids.contains(&asset_id) {
+                let rewritten = format!("rbxassetid://{}", asset_id);
+                if rewritten != url {
+                    change(SanitizeAction::RewroteAssetUrl, format!("{}: {} -> {}", property, url, rewritten));
+                    mutations.push((instance.referent(), Mutation::SetProperty(property.clone(), Variant::Content(Content::from(rewritten)))));
+                }
+            } else {
+                change(SanitizeAction::RemovedAssetUrl, format!("{}: {}", property, url));
+                mutations.push((instance.referent(), Mutation::SetProperty(property.clone(), Variant::Content(Content::new()))));
+            }
+        }
+    }
+
+    true
+}
+
+fn search_for_sanitization(backend: &Backend, policy: &SanitizePolicy, dom: &WeakDom, mutations: &mut Vec<(Ref, Mutation)>, changes: &mut Vec<SanitizeChange>, mut names: Vec<String>, instance: &Instance) {
+    names.push(instance.name.clone());
+
+    if !plan_for_instance(backend, policy, instance, &names.join("."), mutations, changes) {
+        return
+    }
+
+    for &child_ref in instance.children() {
+        if let Some(child) = dom.get_by_ref(child_ref) {
+            search_for_sanitization(backend, policy, dom, mutations, changes, names.clone(), child);
+        }
+    }
+}
+
+impl Backend {
+    pub fn dom_sanitize(&self, dom: &mut WeakDom, policy: &SanitizePolicy) -> Vec<SanitizeChange> {
+        let mut mutations: Vec<(Ref, Mutation)> = Vec::new();
+        let mut changes: Vec<SanitizeChange> = Vec::new();
+
+        for &instance_ref in dom.root().children() {
+            if let Some(instance) = dom.get_by_ref(instance_ref) {
+                search_for_sanitization(self, policy, dom, &mut mutations, &mut changes, Vec::new(), instance);
+            }
+        }
+
+        for (referent, mutation) in mutations {
+            match mutation {
+                Mutation::Destroy => dom.destroy(referent),
+                Mutation::RemoveProperty(property) => {
+                    if let Some(instance) = dom.get_by_ref_mut(referent) {
+                        instance.properties.remove(&property);
+                    }
+                },
+                Mutation::SetProperty(property, value) => {
+                    if let Some(instance) = dom.get_by_ref_mut(referent) {
+                        instance.properties.insert(property, value);
+                    }
+                }
+            }
+        }
+
+        changes
+    }
+
+    pub fn sanitize_model_bytes(&self, bytes: Vec<u8>, policy: &SanitizePolicy) -> Result<SanitizedModel, Box<dyn std::error::Error>> {
+        let mut dom = self.dom_from_bytes(bytes)?;
+        let changes = self.dom_sanitize(&mut dom, policy);
+
+        Ok(SanitizedModel { bytes: self.dom_to_bytes(&dom)?, changes })
+    }
+}
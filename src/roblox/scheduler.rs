@@ -0,0 +1,358 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use tokio::sync::{Semaphore, SemaphorePermit};
+use crate::Backend;
+
+const RETRY_AFTER_HEADER: &str = "retry-after";
+const RATELIMIT_REMAINING_HEADER: &str = "x-ratelimit-remaining";
+const RATELIMIT_RESET_HEADER: &str = "x-ratelimit-reset";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum RobloxEndpoint {
+    Auth,
+    AssetDelivery,
+    Catalog,
+    Cdn,
+    Economy,
+    Inventory,
+    Groups,
+    Thumbnails,
+    Users
+}
+
+const ENDPOINTS: [RobloxEndpoint; 9] = [
+    RobloxEndpoint::Auth,
+    RobloxEndpoint::AssetDelivery,
+    RobloxEndpoint::Catalog,
+    RobloxEndpoint::Cdn,
+    RobloxEndpoint::Economy,
+    RobloxEndpoint::Inventory,
+    RobloxEndpoint::Groups,
+    RobloxEndpoint::Thumbnails,
+    RobloxEndpoint::Users
+];
+
+// Server errors can come after the request was processed, so they're only retried when sending twice is harmless
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum RetryPolicy {
+    Idempotent,
+    // e.g. purchases, a 429 means Roblox never processed the request
+    ThrottledOnly
+}
+
+#[derive(Debug, Clone)]
+pub struct SchedulerConfig {
+    pub max_concurrent_per_endpoint: usize,
+    // Callers waiting for a slot past this many get an error instead of queueing
+    pub max_queued: usize,
+    pub max_retries: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration
+}
+
+impl Default for SchedulerConfig {
+    fn default() -> Self {
+        Self {
+            max_concurrent_per_endpoint: 4,
+            max_queued: 256,
+            max_retries: 4,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30)
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct SchedulerStats {
+    pub requests: u64,
+    pub throttled: u64,
+    pub retried: u64,
+    pub rejected: u64,
+    pub queued: usize
+}
+
+// What the scheduler needs to know about a response
+pub(crate) trait RateLimitedResponse {
+    fn status_code(&self) -> u16;
+    fn header_value(&self, name: &str) -> Option<String>;
+}
+
+impl RateLimitedResponse for reqwest::Response {
+    fn status_code(&self) -> u16 {
+        self.status().as_u16()
+    }
+
+    fn header_value(&self, name: &str) -> Option<String> {
+        self.headers().get(name).and_then(|value| value.to_str().ok()).map(|value| value.to_string())
+    }
+}
+
+fn header_seconds<R: RateLimitedResponse>(response: &R, name: &str) -> Option<Duration> {
+    response
+        .header_value(name)
+        .and_then(|value| value.trim().parse::<f64>().ok())
+        .filter(|seconds| seconds.is_finite() && *seconds >= 0.0)
+        .map(Duration::from_secs_f64)
+}
+
+pub(crate) struct RequestScheduler {
+    config: SchedulerConfig,
+    slots: HashMap<RobloxEndpoint, Semaphore>,
+    paused_until: Mutex<HashMap<RobloxEndpoint, Instant>>,
+    queued: AtomicUsize,
+    requests: AtomicU64,
+    throttled: AtomicU64,
+    retried: AtomicU64,
+    rejected: AtomicU64
+}
+
+impl RequestScheduler {
+    pub(crate) fn new(config: SchedulerConfig) -> Self {
+        let slots = ENDPOINTS
+            .iter()
+            .map(|endpoint| (*endpoint, Semaphore::new(config.max_concurrent_per_endpoint.max(1))))
+            .collect();
+
+        Self {
+            config,
+            slots,
+            paused_until: Mutex::new(HashMap::new()),
+            queued: AtomicUsize::new(0),
+            requests: AtomicU64::new(0),
+            throttled: AtomicU64::new(0),
+            retried: AtomicU64::new(0),
+            rejected: AtomicU64::new(0)
+        }
+    }
+
+    fn stats(&self) -> SchedulerStats {
+        SchedulerStats {
+            requests: self.requests.load(Ordering::Relaxed),
+            throttled: self.throttled.load(Ordering::Relaxed),
+            retried: self.retried.load(Ordering::Relaxed),
+            rejected: self.rejected.load(Ordering::Relaxed),
+            queued: self.queued.load(Ordering::Relaxed)
+        }
+    }
+
+    fn backoff_delay(&self, attempt: u32) -> Duration {
+        let exponential = self.config.base_delay.saturating_mul(2u32.saturating_pow(attempt));
+        let capped = exponential.min(self.config.max_delay);
+
+        // Equal jitter, so retries from concurrent callers don't line up
+        let half = capped / 2;
+        half + half.mul_f64(rand::thread_rng().gen::<f64>())
+    }
+
+    fn pause(&self, endpoint: RobloxEndpoint, duration: Duration) {
+        let until = Instant::now() + duration.min(self.config.max_delay);
+        let mut paused_until = self.paused_until.lock().unwrap();
+        let entry = paused_until.entry(endpoint).or_insert(until);
+        if *entry < until {
+            *entry = until;
+        }
+    }
+
+    async fn wait_if_paused(&self, endpoint: RobloxEndpoint) {
+        let paused_until = self.paused_until.lock().unwrap().get(&endpoint).copied();
+        if let Some(until) = paused_until {
+            let now = Instant::now();
+            if until > now {
+                tokio::time::sleep(until - now).await;
+            }
+        }
+    }
+
+    async fn acquire(&self, endpoint: RobloxEndpoint) -> Result<SemaphorePermit<'_>, Box<dyn std::error::Error>> {
+        if self.queued.fetch_add(1, Ordering::SeqCst) >= self.config.max_queued {
+            self.queued.fetch_sub(1, Ordering::SeqCst);
+            self.rejected.fetch_add(1, Ordering::Relaxed);
+            return Err("Roblox request queue is full.".into())
+        }
+
+        let permit = self.slots[&endpoint].acquire().await;
+        self.queued.fetch_sub(1, Ordering::SeqCst);
+
+        Ok(permit?)
+    }
+
+    pub(crate) async fn execute<F, Fut, R>(&self, endpoint: RobloxEndpoint, send: F) -> Result<R, Box<dyn std::error::Error>>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<R, Box<dyn std::error::Error>>>,
+        R: RateLimitedResponse
+    {
+        self.execute_with_policy(endpoint, RetryPolicy::Idempotent, send).await
+    }
+
+    // `send` is called again for every retry, so it has to build the request from scratch
+    pub(crate) async fn execute_with_policy<F, Fut, R>(&self, endpoint: RobloxEndpoint, policy: RetryPolicy, mut send: F) -> Result<R, Box<dyn std::error::Error>>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<R, Box<dyn std::error::Error>>>,
+        R: RateLimitedResponse
+    {
+        let _permit = self.acquire(endpoint).await?;
+
+        let mut attempt: u32 = 0;
+        loop {
+            self.wait_if_paused(endpoint).await;
+            self.requests.fetch_add(1, Ordering::Relaxed);
+            let response = send().await?;
+
+            let status = response.status_code();
+            let throttled = status == 429;
+            let remaining = response
+                .header_value(RATELIMIT_REMAINING_HEADER)
+                .and_then(|value| value.trim().parse::<u64>().ok());
+            let reset = header_seconds(&response, RATELIMIT_RESET_HEADER);
+
+            if throttled {
+                self.throttled.fetch_add(1, Ordering::Relaxed);
+            }
+            if let (Some(0), Some(reset)) = (remaining, reset) {
+                self.pause(endpoint, reset);
+            }
+
+            let retryable = throttled || (policy == RetryPolicy::Idempotent && (500..600).contains(&status));
+            if !retryable || attempt >= self.config.max_retries {
+                return Ok(response)
+            }
+
+            let delay = header_seconds(&response, RETRY_AFTER_HEADER)
+                .or(if throttled { reset } else { None })
+                .map(|delay| delay.min(self.config.max_delay))
+                .unwrap_or_else(|| self.backoff_delay(attempt));
+
+            self.retried.fetch_add(1, Ordering::Relaxed);
+            attempt += 1;
+            tokio::time::sleep(delay).await;
+        }
+    }
+}
+
+impl Backend {
+    pub fn set_request_scheduler_config(&mut self, config: SchedulerConfig) {
+        self.scheduler = RequestScheduler::new(config);
+    }
+
+    pub fn roblox_request_stats(&self) -> SchedulerStats {
+        self.scheduler.stats()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::time::{Duration, Instant};
+    use tokio::sync::Notify;
+    use super::{RateLimitedResponse, RequestScheduler, RetryPolicy, RobloxEndpoint, SchedulerConfig};
+
+    struct FakeResponse {
+        status: u16,
+        headers: Vec<(&'static str, &'static str)>
+    }
+
+    impl FakeResponse {
+        fn new(status: u16) -> Self {
+            Self { status, headers: Vec::new() }
+        }
+    }
+
+    impl RateLimitedResponse for FakeResponse {
+        fn status_code(&self) -> u16 {
+            self.status
+        }
+
+        fn header_value(&self, name: &str) -> Option<String> {
+            self.headers.iter().find(|(header, _)| *header == name).map(|(_, value)| value.to_string())
+        }
+    }
+
+    #[tokio::test]
+    async fn throttled_requests_wait_for_retry_after() {
+        // Backing off instead of following Retry-After would take at least half a second
+        let scheduler = RequestScheduler::new(SchedulerConfig { base_delay: Duration::from_secs(1), max_delay: Duration::from_secs(1), ..SchedulerConfig::default() });
+        let sent = AtomicU32::new(0);
+
+        let started = Instant::now();
+        let response = scheduler.execute(RobloxEndpoint::Economy, || async {
+            match sent.fetch_add(1, Ordering::SeqCst) {
+                0 => Ok(FakeResponse { status: 429, headers: vec![("retry-after", "0.05")] }),
+                _ => Ok(FakeResponse::new(200))
+            }
+        }).await.unwrap();
+        let elapsed = started.elapsed();
+
+        assert_eq!(response.status, 200);
+        assert_eq!(sent.load(Ordering::SeqCst), 2);
+        assert!(elapsed >= Duration::from_millis(50) && elapsed < Duration::from_millis(400), "waited {:?}", elapsed);
+
+        let stats = scheduler.stats();
+        assert_eq!((stats.requests, stats.throttled, stats.retried), (2, 1, 1));
+    }
+
+    #[tokio::test]
+    async fn server_errors_stop_retrying_at_max_retries() {
+        let scheduler = RequestScheduler::new(SchedulerConfig { max_retries: 2, base_delay: Duration::from_millis(1), ..SchedulerConfig::default() });
+        let sent = AtomicU32::new(0);
+
+        let response = scheduler.execute(RobloxEndpoint::Economy, || async {
+            sent.fetch_add(1, Ordering::SeqCst);
+            Ok(FakeResponse::new(503))
+        }).await.unwrap();
+
+        assert_eq!(response.status, 503);
+        assert_eq!(sent.load(Ordering::SeqCst), 3);
+        assert_eq!(scheduler.stats().retried, 2);
+    }
+
+    #[tokio::test]
+    async fn non_idempotent_requests_are_only_retried_when_throttled() {
+        let scheduler = RequestScheduler::new(SchedulerConfig { base_delay: Duration::from_millis(1), ..SchedulerConfig::default() });
+        let sent = AtomicU32::new(0);
+
+        let response = scheduler.execute_with_policy(RobloxEndpoint::Economy, RetryPolicy::ThrottledOnly, || async {
+            match sent.fetch_add(1, Ordering::SeqCst) {
+                0 => Ok(FakeResponse { status: 429, headers: vec![("retry-after", "0")] }),
+                _ => Ok(FakeResponse::new(500))
+            }
+        }).await.unwrap();
+
+        assert_eq!(response.status, 500);
+        assert_eq!(sent.load(Ordering::SeqCst), 2);
+        assert_eq!(scheduler.stats().retried, 1);
+    }
+
+    #[tokio::test]
+    async fn callers_past_max_queued_are_rejected() {
+        let scheduler = RequestScheduler::new(SchedulerConfig { max_concurrent_per_endpoint: 1, max_queued: 1, ..SchedulerConfig::default() });
+        let release = Notify::new();
+
+        // The first request holds the only slot, the second queues for it and the third has no room left
+        let (first, second, third) = tokio::join!(
+            scheduler.execute(RobloxEndpoint::Economy, || async {
+                release.notified().await;
+                Ok(FakeResponse::new(200))
+            }),
+            scheduler.execute(RobloxEndpoint::Economy, || async { Ok(FakeResponse::new(200)) }),
+            async {
+                let third = scheduler.execute(RobloxEndpoint::Economy, || async { Ok(FakeResponse::new(200)) }).await;
+                release.notify_one();
+                third
+            }
+        );
+
+        assert_eq!(first.unwrap().status, 200);
+        assert_eq!(second.unwrap().status, 200);
+        assert_eq!(third.err().map(|error| error.to_string()), Some("Roblox request queue is full.".to_string()));
+
+        let stats = scheduler.stats();
+        assert_eq!((stats.requests, stats.rejected, stats.queued), (2, 1, 0));
+    }
+}
@@ -0,0 +1,95 @@
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use crate::Backend;
+use super::accounts::RobloxAccount;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct RobloxSessionStatus {
+    #[serde(rename = "accountIndex")]
+    pub account_index: usize,
+    pub valid: bool,
+    #[serde(rename = "userId")]
+    pub user_id: Option<u64>,
+    pub username: Option<String>,
+    // Set when the session couldn't be checked, e.g. Roblox was unreachable, in which case `valid` says nothing
+    pub error: Option<String>
+}
+
+impl Backend {
+    async fn check_account_session(&self, account_index: usize, account: &RobloxAccount) -> Result<RobloxSessionStatus, Box<dyn std::error::Error>> {
+        let status = match self.fetch_authenticated_user_internal(account).await? {
+            Some(user) => {
+                account.set_user_id(user.id);
+                account.mark_healthy();
+                RobloxSessionStatus { account_index, valid: true, user_id: Some(user.id), username: Some(user.name), error: None }
+            },
+            None => RobloxSessionStatus { account_index, valid: false, user_id: account.user_id(), username: None, error: None }
+        };
+
+        Ok(status)
+    }
+
+    pub(super) async fn account_user_id(&self, account: &RobloxAccount) -> Result<u64, Box<dyn std::error::Error>> {
+        if let Some(user_id) = account.user_id() {
+            return Ok(user_id)
+        }
+
+        match self.fetch_authenticated_user_internal(account).await? {
+            Some(user) => {
+                account.set_user_id(user.id);
+                Ok(user.id)
+            },
+            None => Err("Roblox session is no longer valid.".into())
+        }
+    }
+
+    // Status of the account passed to `Backend::new`
+    pub async fn roblox_session_status(&self) -> Result<RobloxSessionStatus, Box<dyn std::error::Error>> {
+        match self.accounts.get(0) {
+            Some(account) => self.check_account_session(0, account).await,
+            None => Err("Roblox account does not exist.".into())
+        }
+    }
+
+    // One account failing to be checked doesn't hide the others, its status carries the error instead
+    pub async fn roblox_session_statuses(&self) -> Vec<RobloxSessionStatus> {
+        let mut statuses: Vec<RobloxSessionStatus> = Vec::new();
+        for index in 0..self.accounts.len() {
+            if let Some(account) = self.accounts.get(index) {
+                let status = match self.check_account_session(index, account).await {
+                    Ok(status) => status,
+                    Err(error) => RobloxSessionStatus { account_index: index, valid: false, user_id: account.user_id(), username: None, error: Some(error.to_string()) }
+                };
+                statuses.push(status);
+            }
+        }
+
+        statuses
+    }
+
+    // Never returns, meant to be spawned next to the application. `on_invalid` is called once for
+    // every account whose session goes from valid (or unchecked) to invalid.
+    pub async fn watch_roblox_session<F>(&self, interval: Duration, mut on_invalid: F)
+    where
+        F: FnMut(RobloxSessionStatus)
+    {
+        let mut was_valid: Vec<bool> = vec![true; self.accounts.len()];
+        loop {
+            for status in self.roblox_session_statuses().await {
+                // Network errors don't say anything about the session, so those accounts are skipped until the next check
+                if status.error.is_some() {
+                    continue
+                }
+                if status.account_index >= was_valid.len() {
+                    was_valid.resize(status.account_index + 1, true);
+                }
+                if was_valid[status.account_index] && !status.valid {
+                    on_invalid(status.clone());
+                }
+                was_valid[status.account_index] = status.valid;
+            }
+
+            tokio::time::sleep(interval).await;
+        }
+    }
+}
@@ -1,16 +1,99 @@
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use serde_repr::{Serialize_repr, Deserialize_repr};
 
-#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Serialize_repr, Deserialize_repr)]
-#[repr(u8)]
-pub enum AssetType {
-    #[default]
+// Generates AssetType along with its conversions from/to the numeric IDs Roblox uses
+macro_rules! asset_types {
+    ($($name:ident = $id:literal),* $(,)?) => {
+        // IDs Roblox adds later end up in Unknown instead of failing the whole response
+        #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Serialize, Deserialize)]
+        #[serde(from = "u16", into = "u16")]
+        pub enum AssetType {
+            // The first listed type is the default
+            #[default]
+            $($name,)*
+            Unknown(u16)
+        }
+
+        impl From<u16> for AssetType {
+            fn from(id: u16) -> Self {
+                match id {
+                    $($id => AssetType::$name,)*
+                    other => AssetType::Unknown(other)
+                }
+            }
+        }
+
+        impl From<AssetType> for u16 {
+            fn from(asset_type: AssetType) -> Self {
+                match asset_type {
+                    $(AssetType::$name => $id,)*
+                    AssetType::Unknown(id) => id
+                }
+            }
+        }
+    };
+}
+
+asset_types! {
     Image = 1,
+    TShirt = 2,
     Audio = 3,
     Mesh = 4,
     Lua = 5,
+    Hat = 8,
+    Place = 9,
     Model = 10,
-    Decal = 13
+    Shirt = 11,
+    Pants = 12,
+    Decal = 13,
+    Head = 17,
+    Face = 18,
+    Gear = 19,
+    Badge = 21,
+    Animation = 24,
+    Torso = 27,
+    RightArm = 28,
+    LeftArm = 29,
+    LeftLeg = 30,
+    RightLeg = 31,
+    Package = 32,
+    GamePass = 34,
+    Plugin = 38,
+    MeshPart = 40,
+    HairAccessory = 41,
+    FaceAccessory = 42,
+    NeckAccessory = 43,
+    ShoulderAccessory = 44,
+    FrontAccessory = 45,
+    BackAccessory = 46,
+    WaistAccessory = 47,
+    ClimbAnimation = 48,
+    DeathAnimation = 49,
+    FallAnimation = 50,
+    IdleAnimation = 51,
+    JumpAnimation = 52,
+    RunAnimation = 53,
+    SwimAnimation = 54,
+    WalkAnimation = 55,
+    PoseAnimation = 56,
+    EarAccessory = 57,
+    EyeAccessory = 58,
+    EmoteAnimation = 61,
+    Video = 62,
+    TShirtAccessory = 64,
+    ShirtAccessory = 65,
+    PantsAccessory = 66,
+    JacketAccessory = 67,
+    SweaterAccessory = 68,
+    ShortsAccessory = 69,
+    LeftShoeAccessory = 70,
+    RightShoeAccessory = 71,
+    DressSkirtAccessory = 72,
+    FontFamily = 73,
+    EyebrowAccessory = 76,
+    EyelashAccessory = 77,
+    MoodAnimation = 78,
+    DynamicHead = 79
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Serialize, Deserialize)]
@@ -57,6 +140,45 @@ pub struct ItemDetails {
     pub is_for_sale: Option<bool>,
     #[serde(rename = "IsPublicDomain")]
     pub is_public_domain: Option<bool>,
+    #[serde(rename = "IconImageAssetId")]
+    pub icon_image_asset_id: Option<u64>,
+    #[serde(rename = "Created")]
+    pub created: Option<DateTime<Utc>>,
+    #[serde(rename = "Updated")]
+    pub updated: Option<DateTime<Utc>>,
+    #[serde(rename = "Sales")]
+    pub sales: Option<u64>,
+    #[serde(rename = "IsLimited")]
+    pub is_limited: Option<bool>,
+    #[serde(rename = "IsLimitedUnique")]
+    pub is_limited_unique: Option<bool>,
+    #[serde(rename = "Remaining")]
+    pub remaining: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum ThumbnailState {
+    Completed,
+    Pending,
+    Blocked,
+    InReview,
+    Error,
+    #[serde(other)]
+    Unknown
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Thumbnail {
+    #[serde(rename = "targetId")]
+    pub target_id: u64,
+    pub state: ThumbnailState,
+    #[serde(rename = "imageUrl")]
+    pub image_url: Option<String>
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ThumbnailsResponse {
+    pub data: Vec<Thumbnail>
 }
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct AssetPurchaseReq {
@@ -67,19 +189,167 @@ pub struct AssetPurchaseReq {
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct AssetDeliveryLocation {
-    #[serde(rename = "assetFormat")]
-    pub asset_format: String,
-    pub location: String
+pub struct AuthenticatedUser {
+    pub id: u64,
+    pub name: String,
+    #[serde(rename = "displayName")]
+    pub display_name: String
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RobloxUser {
+    pub id: u64,
+    pub name: String,
+    #[serde(rename = "displayName")]
+    pub display_name: String,
+    #[serde(default)]
+    pub description: String,
+    pub created: DateTime<Utc>,
+    // Banned (or deleted) on Roblox itself, not in our own ban list
+    #[serde(rename = "isBanned")]
+    pub is_banned: bool
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct UsernamesLookupReq {
+    pub usernames: Vec<String>,
+    #[serde(rename = "excludeBannedUsers")]
+    pub exclude_banned_users: bool
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct UsernameLookupResult {
+    #[serde(rename = "requestedUsername")]
+    pub requested_username: String,
+    pub id: u64,
+    pub name: String
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct UsernamesLookupResponse {
+    pub data: Vec<UsernameLookupResult>
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct UserIdsLookupReq {
+    #[serde(rename = "userIds")]
+    pub user_ids: Vec<u64>,
+    #[serde(rename = "excludeBannedUsers")]
+    pub exclude_banned_users: bool
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct UserIdLookupResult {
+    pub id: u64,
+    pub name: String,
+    #[serde(rename = "displayName")]
+    pub display_name: String
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct UserIdsLookupResponse {
+    pub data: Vec<UserIdLookupResult>
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CatalogItemReq {
+    #[serde(rename = "itemType")]
+    pub item_type: String,
+    pub id: u64
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CatalogItemsDetailsReq {
+    pub items: Vec<CatalogItemReq>
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CatalogItemDetails {
+    pub id: u64,
+    #[serde(rename = "assetType")]
+    pub asset_type: Option<AssetType>,
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(rename = "productId", default)]
+    pub product_id: i64,
+    #[serde(rename = "creatorHasVerifiedBadge", default)]
+    pub creator_has_verified_badge: bool,
+    #[serde(rename = "creatorType")]
+    pub creator_type: CreatorType,
+    #[serde(rename = "creatorTargetId")]
+    pub creator_target_id: i64,
+    #[serde(rename = "creatorName")]
+    pub creator_name: String,
+    pub price: Option<u64>,
+    #[serde(rename = "isOffSale", default)]
+    pub is_off_sale: bool,
+    #[serde(rename = "itemRestrictions", default)]
+    pub item_restrictions: Vec<String>,
+    #[serde(rename = "unitsAvailableForConsumption")]
+    pub units_available: Option<u64>
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CatalogItemsDetailsResponse {
+    pub data: Vec<CatalogItemDetails>
+}
+
+// The catalog has no creation dates, sales or public domain flag, those are left empty
+impl From<CatalogItemDetails> for ItemDetails {
+    fn from(item: CatalogItemDetails) -> Self {
+        let is_restricted = |restriction: &str| item.item_restrictions.iter().any(|r| r == restriction);
+
+        ItemDetails {
+            id: item.id as i64,
+            target_id: item.id as i64,
+            product_id: item.product_id,
+            asset_type_id: item.asset_type,
+            is_for_sale: Some(!item.is_off_sale),
+            is_public_domain: None,
+            icon_image_asset_id: None,
+            created: None,
+            updated: None,
+            sales: None,
+            is_limited: Some(is_restricted("Limited")),
+            is_limited_unique: Some(is_restricted("LimitedUnique")),
+            remaining: item.units_available,
+            price_in_robux: item.price,
+            creator: Creator {
+                id: item.creator_target_id,
+                has_verified_badge: item.creator_has_verified_badge,
+                creator_type: item.creator_type,
+                target_id: item.creator_target_id,
+                name: item.creator_name
+            },
+            name: item.name,
+            description: item.description
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GroupRole {
+    pub id: u64,
+    pub name: String,
+    pub rank: u8
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GroupSummary {
+    pub id: u64,
+    pub name: String
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct UserGroupRole {
+    pub group: GroupSummary,
+    pub role: GroupRole
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct AssetDeliveryResponse {
-    pub locations: Vec<AssetDeliveryLocation>,
-    #[serde(rename = "requestId")]
-    pub request_id: String,
-    #[serde(rename = "assetTypeId")]
-    pub asset_type_id: AssetType,
+pub struct UserGroupRolesResponse {
+    pub data: Vec<UserGroupRole>
 }
 
 // {"errors":[{"code":0,"message":"User is not authorized to access Asset."}]}
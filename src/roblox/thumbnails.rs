@@ -0,0 +1,155 @@
+use std::collections::{BTreeSet, HashMap};
+use std::time::Duration;
+use futures::stream::{self, StreamExt};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use crate::Backend;
+use super::batch::{BatchResult, BATCH_CONCURRENCY};
+use super::download::AssetDownloadStream;
+use super::internal::error_from_response;
+use super::scheduler::RobloxEndpoint;
+use super::structs::{Thumbnail, ThumbnailState};
+
+// The thumbnails endpoint takes at most this many asset IDs per request
+const THUMBNAIL_BATCH_SIZE: usize = 100;
+// Freshly uploaded assets stay Pending for a few seconds while Roblox renders them
+const THUMBNAIL_PENDING_RETRIES: u32 = 5;
+const THUMBNAIL_PENDING_DELAY: Duration = Duration::from_secs(2);
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ThumbnailFormat {
+    #[default]
+    Png,
+    Jpeg,
+    Webp
+}
+
+impl ThumbnailFormat {
+    fn as_str(&self) -> &'static str {
+        match self {
+            ThumbnailFormat::Png => "Png",
+            ThumbnailFormat::Jpeg => "Jpeg",
+            ThumbnailFormat::Webp => "Webp"
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AssetThumbnail {
+    #[serde(rename = "assetId")]
+    pub asset_id: u64,
+    // Can still be Pending if Roblox didn't finish rendering it in time
+    pub state: ThumbnailState,
+    #[serde(rename = "imageUrl")]
+    pub image_url: Option<String>,
+    // Only filled in when the image was asked for and the thumbnail is Completed
+    #[serde(skip)]
+    pub bytes: Option<Vec<u8>>
+}
+
+fn thumbnail_cache_variant(size: &str, format: ThumbnailFormat) -> String {
+    format!("thumbnail_{}_{}", size, format.as_str().to_lowercase())
+}
+
+impl Backend {
+    // Resolves the thumbnails in batches, asking again for the ones that are still being rendered
+    async fn resolve_asset_thumbnails(&self, asset_ids: &[u64], size: &str, format: ThumbnailFormat) -> BatchResult<Thumbnail> {
+        let mut results: BatchResult<Thumbnail> = HashMap::new();
+        let mut pending: Vec<u64> = asset_ids.iter().copied().collect::<BTreeSet<u64>>().into_iter().collect();
+
+        for attempt in 0..=THUMBNAIL_PENDING_RETRIES {
+            for chunk in pending.chunks(THUMBNAIL_BATCH_SIZE) {
+                match self.fetch_asset_thumbnails_internal(chunk, size, format.as_str()).await {
+                    Ok(thumbnails) => {
+                        for thumbnail in thumbnails {
+                            results.insert(thumbnail.target_id, Ok(thumbnail));
+                        }
+                    },
+                    Err(error) => {
+                        let message = error.to_string();
+                        for asset_id in chunk {
+                            results.insert(*asset_id, Err(message.clone().into()));
+                        }
+                    }
+                }
+            }
+
+            pending = results
+                .iter()
+                .filter(|(_, result)| matches!(result, Ok(thumbnail) if thumbnail.state == ThumbnailState::Pending))
+                .map(|(asset_id, _)| *asset_id)
+                .collect();
+            if pending.is_empty() || attempt == THUMBNAIL_PENDING_RETRIES {
+                break
+            }
+            tokio::time::sleep(THUMBNAIL_PENDING_DELAY).await;
+        }
+
+        for asset_id in asset_ids {
+            results.entry(*asset_id).or_insert_with(|| Err("Roblox did not return a thumbnail for the asset.".into()));
+        }
+
+        results
+    }
+
+    async fn download_thumbnail_image(&self, asset_id: u64, image_url: &str, size: &str, format: ThumbnailFormat) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let variant = thumbnail_cache_variant(size, format);
+        if let Ok(Some(bytes)) = self.asset_cache_get_variant(asset_id, &variant).await {
+            return Ok(bytes)
+        }
+
+        let request_result = self.scheduler.execute(RobloxEndpoint::Cdn, || async {
+            Ok(self.http_client
+                .get(image_url)
+                .send()
+                .await?)
+        }).await?;
+        if request_result.status() != StatusCode::OK {
+            return Err(error_from_response(request_result).await)
+        }
+        // Thumbnails are held back by the same limit as assets, the CDN URL comes from Roblox's response
+        let bytes = AssetDownloadStream::new(request_result, self.max_asset_download_size)?.read_to_end().await?;
+
+        let _ = self.asset_cache_put_variant(asset_id, &variant, &bytes).await;
+        Ok(bytes)
+    }
+
+    async fn thumbnail_with_image(&self, thumbnail: Thumbnail, size: &str, format: ThumbnailFormat, download: bool) -> Result<AssetThumbnail, Box<dyn std::error::Error>> {
+        let bytes = match (&thumbnail.state, &thumbnail.image_url) {
+            (ThumbnailState::Completed, Some(image_url)) if download => {
+                Some(self.download_thumbnail_image(thumbnail.target_id, image_url, size, format).await?)
+            },
+            _ => None
+        };
+
+        Ok(AssetThumbnail {
+            asset_id: thumbnail.target_id,
+            state: thumbnail.state,
+            image_url: thumbnail.image_url,
+            bytes
+        })
+    }
+
+    // `size` is one of the sizes Roblox renders asset thumbnails at, e.g. "150x150" or "420x420"
+    pub async fn get_asset_thumbnail(&self, asset_id: u64, size: &str, format: ThumbnailFormat, download: bool) -> Result<AssetThumbnail, Box<dyn std::error::Error>> {
+        match self.get_asset_thumbnails(&[asset_id], size, format, download).await.remove(&asset_id) {
+            Some(result) => result,
+            None => Err("Roblox did not return a thumbnail for the asset.".into())
+        }
+    }
+
+    pub async fn get_asset_thumbnails(&self, asset_ids: &[u64], size: &str, format: ThumbnailFormat, download: bool) -> BatchResult<AssetThumbnail> {
+        let resolved = self.resolve_asset_thumbnails(asset_ids, size, format).await;
+
+        stream::iter(resolved)
+            .map(|(asset_id, result)| async move {
+                match result {
+                    Ok(thumbnail) => (asset_id, self.thumbnail_with_image(thumbnail, size, format, download).await),
+                    Err(error) => (asset_id, Err(error))
+                }
+            })
+            .buffer_unordered(BATCH_CONCURRENCY)
+            .collect()
+            .await
+    }
+}
@@ -0,0 +1,163 @@
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use futures::stream::{self, StreamExt};
+use crate::Backend;
+use crate::utils::datetime_now;
+use super::batch::{BatchResult, BATCH_CONCURRENCY};
+use super::structs::RobloxUser;
+
+// Usernames can change, so nothing is trusted for longer than this
+const USER_CACHE_TTL: Duration = Duration::from_secs(10 * 60);
+// The usernames and user IDs endpoints take at most this many per request
+const USERNAME_BATCH_SIZE: usize = 100;
+
+#[derive(Default)]
+pub(crate) struct UserCache {
+    users: Mutex<HashMap<u64, (RobloxUser, u64)>>,
+    // Keyed by the lowercased username, usernames are case insensitive on Roblox
+    usernames: Mutex<HashMap<String, (u64, u64)>>,
+    // User ID to username, for lookups that don't need the whole user
+    names: Mutex<HashMap<u64, (String, u64)>>,
+    last_pruned: AtomicU64
+}
+
+fn is_fresh(cached_at: u64) -> bool {
+    datetime_now().saturating_sub(cached_at) <= USER_CACHE_TTL.as_millis() as u64
+}
+
+impl UserCache {
+    // Drops expired entries at most once per TTL, so the cache only holds recent lookups
+    fn prune_expired(&self) {
+        let now = datetime_now();
+        let last_pruned = self.last_pruned.load(Ordering::Relaxed);
+        if now.saturating_sub(last_pruned) < USER_CACHE_TTL.as_millis() as u64 {
+            return
+        }
+        if self.last_pruned.compare_exchange(last_pruned, now, Ordering::Relaxed, Ordering::Relaxed).is_err() {
+            return
+        }
+
+        self.users.lock().unwrap().retain(|_, (_, cached_at)| is_fresh(*cached_at));
+        self.usernames.lock().unwrap().retain(|_, (_, cached_at)| is_fresh(*cached_at));
+        self.names.lock().unwrap().retain(|_, (_, cached_at)| is_fresh(*cached_at));
+    }
+
+    fn get_user(&self, user_id: u64) -> Option<RobloxUser> {
+        let users = self.users.lock().unwrap();
+        users.get(&user_id).filter(|(_, cached_at)| is_fresh(*cached_at)).map(|(user, _)| user.clone())
+    }
+
+    fn put_user(&self, user: &RobloxUser) {
+        self.prune_expired();
+        let now = datetime_now();
+        self.usernames.lock().unwrap().insert(user.name.to_lowercase(), (user.id, now));
+        self.names.lock().unwrap().insert(user.id, (user.name.clone(), now));
+        self.users.lock().unwrap().insert(user.id, (user.clone(), now));
+    }
+
+    fn get_username(&self, user_id: u64) -> Option<String> {
+        let names = self.names.lock().unwrap();
+        names.get(&user_id).filter(|(_, cached_at)| is_fresh(*cached_at)).map(|(name, _)| name.clone())
+    }
+
+    fn put_username(&self, user_id: u64, username: &str) {
+        self.prune_expired();
+        let now = datetime_now();
+        self.usernames.lock().unwrap().insert(username.to_lowercase(), (user_id, now));
+        self.names.lock().unwrap().insert(user_id, (username.to_string(), now));
+    }
+
+    fn get_user_id(&self, username: &str) -> Option<u64> {
+        let usernames = self.usernames.lock().unwrap();
+        usernames.get(&username.to_lowercase()).filter(|(_, cached_at)| is_fresh(*cached_at)).map(|(user_id, _)| *user_id)
+    }
+
+    fn put_user_id(&self, username: &str, user_id: u64) {
+        self.prune_expired();
+        self.usernames.lock().unwrap().insert(username.to_lowercase(), (user_id, datetime_now()));
+    }
+}
+
+impl Backend {
+    pub async fn get_user_info(&self, user_id: u64) -> Result<RobloxUser, Box<dyn std::error::Error>> {
+        if let Some(user) = self.user_cache.get_user(user_id) {
+            return Ok(user)
+        }
+
+        match self.fetch_user_internal(user_id).await? {
+            Some(user) => {
+                self.user_cache.put_user(&user);
+                Ok(user)
+            },
+            None => Err("Roblox user does not exist.".into())
+        }
+    }
+
+    // Roblox has no batch endpoint returning created dates and ban status, so this fans out like the asset batches
+    pub async fn get_users_info(&self, user_ids: &[u64]) -> BatchResult<RobloxUser> {
+        let unique_ids: BTreeSet<u64> = user_ids.iter().copied().collect();
+
+        stream::iter(unique_ids)
+            .map(|user_id| async move { (user_id, self.get_user_info(user_id).await) })
+            .buffer_unordered(BATCH_CONCURRENCY)
+            .collect()
+            .await
+    }
+
+    // Users that don't exist are left out, a lot cheaper than `get_users_info` when only names are needed
+    pub async fn get_usernames(&self, user_ids: &[u64]) -> Result<HashMap<u64, String>, Box<dyn std::error::Error>> {
+        let mut usernames: HashMap<u64, String> = HashMap::new();
+        let mut missing: BTreeSet<u64> = BTreeSet::new();
+
+        for user_id in user_ids {
+            match self.user_cache.get_username(*user_id) {
+                Some(username) => { usernames.insert(*user_id, username); },
+                None => { missing.insert(*user_id); }
+            }
+        }
+
+        let missing: Vec<u64> = missing.into_iter().collect();
+        for chunk in missing.chunks(USERNAME_BATCH_SIZE) {
+            for result in self.fetch_users_by_ids_internal(chunk).await? {
+                self.user_cache.put_username(result.id, &result.name);
+                usernames.insert(result.id, result.name);
+            }
+        }
+
+        Ok(usernames)
+    }
+
+    // None when no user has that username
+    pub async fn resolve_username(&self, username: &str) -> Result<Option<u64>, Box<dyn std::error::Error>> {
+        let mut resolved = self.resolve_usernames(&[username.to_string()]).await?;
+        Ok(resolved.remove(&username.to_lowercase()))
+    }
+
+    // Keyed by the lowercased username, usernames that don't exist are left out
+    pub async fn resolve_usernames(&self, usernames: &[String]) -> Result<HashMap<String, u64>, Box<dyn std::error::Error>> {
+        let mut resolved: HashMap<String, u64> = HashMap::new();
+        let mut missing: BTreeSet<String> = BTreeSet::new();
+
+        for username in usernames {
+            let username = username.to_lowercase();
+            match self.user_cache.get_user_id(&username) {
+                Some(user_id) => { resolved.insert(username, user_id); },
+                None => { missing.insert(username); }
+            }
+        }
+
+        let missing: Vec<String> = missing.into_iter().collect();
+        for chunk in missing.chunks(USERNAME_BATCH_SIZE) {
+            for result in self.fetch_users_by_usernames_internal(chunk).await? {
+                let username = result.requested_username.to_lowercase();
+                self.user_cache.put_user_id(&username, result.id);
+                self.user_cache.put_user_id(&result.name, result.id);
+                resolved.insert(username, result.id);
+            }
+        }
+
+        Ok(resolved)
+    }
+}
@@ -0,0 +1,86 @@
+use std::collections::BTreeSet;
+use rbx_dom_weak::WeakDom;
+use serde::{Deserialize, Serialize};
+use crate::Backend;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScriptChangeKind {
+    Added,
+    Removed,
+    Modified
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ScriptChange {
+    pub path: String,
+    pub kind: ScriptChangeKind,
+    pub before: Option<String>,
+    pub after: Option<String>
+}
+
+impl Backend {
+    pub async fn get_asset_latest_version(&self, asset_id: u64) -> Result<Option<u64>, Box<dyn std::error::Error>> {
+        self.fetch_asset_version_internal(asset_id).await
+    }
+
+    pub async fn download_asset_version_bytes(&self, asset_id: u64, version: u64) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        // Versions never change once published, so these are always safe to serve from the cache.
+        // A broken cache shouldn't stop downloads, so its errors count as a miss
+        if let Ok(Some(bytes)) = self.asset_cache_get(asset_id, version).await {
+            return Ok(bytes)
+        }
+
+        let bytes = self.download_asset_internal(asset_id, Some(version)).await?;
+        let _ = self.asset_cache_put(asset_id, version, &bytes).await;
+        Ok(bytes)
+    }
+
+    // Downloads the version that was approved by `whitelist_asset`, rather than whatever was published since
+    pub async fn download_approved_asset_bytes(&self, asset_id: u64) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let whitelisted = match self.find_whitelisted_asset(asset_id).await? {
+            Some(entry) => entry,
+            None => return Err("Asset is not whitelisted.".into())
+        };
+
+        match whitelisted.approved_version {
+            Some(version) => self.download_asset_version_bytes(asset_id, version as u64).await,
+            None => Err("Asset was whitelisted without a known version.".into())
+        }
+    }
+
+    pub fn dom_diff_scripts(&self, before: &WeakDom, after: &WeakDom) -> Vec<ScriptChange> {
+        let before_scripts = self.dom_find_scripts(before);
+        let after_scripts = self.dom_find_scripts(after);
+
+        let paths: BTreeSet<&String> = before_scripts.keys().chain(after_scripts.keys()).collect();
+        let mut changes: Vec<ScriptChange> = Vec::new();
+
+        for path in paths {
+            let before_source = before_scripts.get(path);
+            let after_source = after_scripts.get(path);
+
+            let kind = match (before_source, after_source) {
+                (None, Some(_)) => ScriptChangeKind::Added,
+                (Some(_), None) => ScriptChangeKind::Removed,
+                (Some(old), Some(new)) if old != new => ScriptChangeKind::Modified,
+                _ => continue
+            };
+
+            changes.push(ScriptChange {
+                path: path.clone(),
+                kind,
+                before: before_source.cloned(),
+                after: after_source.cloned()
+            });
+        }
+
+        changes
+    }
+
+    pub async fn diff_asset_version_scripts(&self, asset_id: u64, from_version: u64, to_version: u64) -> Result<Vec<ScriptChange>, Box<dyn std::error::Error>> {
+        let before = self.dom_from_bytes(self.download_asset_version_bytes(asset_id, from_version).await?)?;
+        let after = self.dom_from_bytes(self.download_asset_version_bytes(asset_id, to_version).await?)?;
+
+        Ok(self.dom_diff_scripts(&before, &after))
+    }
+}
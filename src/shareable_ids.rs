@@ -0,0 +1,143 @@
+use std::fmt;
+use std::str::FromStr;
+use serde::{de, ser, Deserialize, Deserializer, Serialize, Serializer};
+use crate::Backend;
+use crate::id_converter::{with_shareable_converter, IDConversionError};
+
+struct IdVisitor<T>(std::marker::PhantomData<T>);
+
+impl<'de, T: FromStr<Err = IDConversionError>> de::Visitor<'de> for IdVisitor<T> {
+    type Value = T;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a prefixed shareable ID")
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<T, E> {
+        value.parse().map_err(E::custom)
+    }
+}
+
+// For stored IDs, see `deserialize_number`
+struct NumberIdVisitor<T>(std::marker::PhantomData<T>);
+
+impl<'de, T: From<u64>> de::Visitor<'de> for NumberIdVisitor<T> {
+    type Value = T;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a non-negative numeric ID")
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<T, E> {
+        Ok(T::from(value))
+    }
+
+    // Mongo only has signed integers
+    fn visit_i64<E: de::Error>(self, value: i64) -> Result<T, E> {
+        match u64::try_from(value) {
+            Ok(value) => Ok(T::from(value)),
+            Err(_) => Err(E::invalid_value(de::Unexpected::Signed(value), &self))
+        }
+    }
+}
+
+macro_rules! shareable_ids {
+    ($($name:ident = $prefix:literal),* $(,)?) => {
+        $(
+            // Displayed, serialized and parsed as its prefixed shareable ID inside `Backend::with_shareable_ids`.
+            // Serializing or parsing outside of it fails rather than exposing the sequential number.
+            // Stored fields use `serialize_with`/`deserialize_with` with `serialize_number` and `deserialize_number`.
+            #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+            pub struct $name(pub u64);
+
+            impl $name {
+                pub const PREFIX: &'static str = $prefix;
+
+                pub fn to_shareable(&self, backend: &Backend) -> Result<String, IDConversionError> {
+                    Ok(format!("{}{}", Self::PREFIX, backend.id_generator.to_short(self.0)?))
+                }
+
+                pub fn from_shareable(input: &str, backend: &Backend) -> Result<Self, IDConversionError> {
+                    Ok(Self(backend.id_generator.to_number(Self::strip_prefix(input)?)?))
+                }
+
+                // The prefix is matched regardless of case, people retype IDs in all sorts of ways
+                fn strip_prefix(input: &str) -> Result<&str, IDConversionError> {
+                    let input = input.trim();
+                    match input.get(..Self::PREFIX.len()) {
+                        Some(prefix) if prefix.eq_ignore_ascii_case(Self::PREFIX) => Ok(&input[Self::PREFIX.len()..]),
+                        _ => Err(IDConversionError::WrongPrefix { expected: Self::PREFIX })
+                    }
+                }
+
+                // NotConfigured outside of `Backend::with_shareable_ids`, Zero for 0, which has no shareable form
+                fn scoped_shareable(&self) -> Result<String, IDConversionError> {
+                    with_shareable_converter(|converter| converter.to_short(self.0))
+                        .map(|short| format!("{}{}", Self::PREFIX, short))
+                }
+
+                // Mongo only has signed integers, IDs past i64::MAX can't be stored
+                pub fn serialize_number<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                    match i64::try_from(self.0) {
+                        Ok(id) => serializer.serialize_i64(id),
+                        Err(_) => Err(ser::Error::custom(IDConversionError::Overflow))
+                    }
+                }
+
+                pub fn deserialize_number<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                    deserializer.deserialize_any(NumberIdVisitor(std::marker::PhantomData))
+                }
+            }
+
+            impl From<u64> for $name {
+                fn from(id: u64) -> Self {
+                    Self(id)
+                }
+            }
+
+            impl From<$name> for u64 {
+                fn from(id: $name) -> Self {
+                    id.0
+                }
+            }
+
+            // Formatting can't fail, so without a shareable form only the prefix and a placeholder are shown
+            impl fmt::Display for $name {
+                fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                    match self.scoped_shareable() {
+                        Ok(shareable) => f.write_str(&shareable),
+                        Err(_) => write!(f, "{}?", Self::PREFIX)
+                    }
+                }
+            }
+
+            impl FromStr for $name {
+                type Err = IDConversionError;
+
+                fn from_str(input: &str) -> Result<Self, Self::Err> {
+                    let short = Self::strip_prefix(input)?;
+                    Ok(Self(with_shareable_converter(|converter| converter.to_number(short))?))
+                }
+            }
+
+            impl Serialize for $name {
+                fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                    serializer.serialize_str(&self.scoped_shareable().map_err(ser::Error::custom)?)
+                }
+            }
+
+            impl<'de> Deserialize<'de> for $name {
+                fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                    deserializer.deserialize_str(IdVisitor(std::marker::PhantomData))
+                }
+            }
+        )*
+    };
+}
+
+// Prefixes end with an underscore, since dashes are left for grouping the characters
+shareable_ids! {
+    MapId = "map_",
+    UserId = "user_",
+    ReplayId = "replay_",
+}
@@ -1,13 +1,5 @@
 use std::time::{SystemTime, UNIX_EPOCH};
 
-pub fn reverse_string(s: &str) -> String {
-    s.chars().rev().collect()
-}
-
-pub fn string_to_static_str(s: String) -> &'static str {
-    Box::leak(s.into_boxed_str())
-}
-
 pub fn datetime_now() -> u64 { // We lose some precision, but it's okay...
     let start = SystemTime::now();
     let since_the_epoch = start
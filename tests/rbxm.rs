@@ -0,0 +1,45 @@
+use liquid_breakout_backend_v2::Backend;
+use rbx_dom_weak::{InstanceBuilder, WeakDom};
+use rbx_types::Content;
+
+fn backend() -> Backend {
+    Backend::new(String::new(), vec!["abcdefghijklmnopqrstuvwxyz".to_string()])
+}
+
+#[test]
+fn asset_references_cover_content_properties_and_requires() {
+    let backend = backend();
+
+    let mut dom = WeakDom::new(InstanceBuilder::new("DataModel"));
+    let model = dom.insert(dom.root_ref(), InstanceBuilder::new("Model").with_name("Model"));
+    dom.insert(model, InstanceBuilder::new("MeshPart")
+        .with_name("Mesh")
+        .with_property("MeshId", Content::from("rbxassetid://101"))
+        .with_property("TextureID", Content::from("http://www.roblox.com/asset/?id=102")));
+    dom.insert(model, InstanceBuilder::new("Sound").with_name("Sound").with_property("SoundId", Content::from("rbxasset://sounds/local.wav")));
+    dom.insert(model, InstanceBuilder::new("ModuleScript").with_name("Loader").with_property("Source", [
+        "local a = function() return require(202) end",
+        "if x then else require(203) end",
+        "print(require(204))",
+        "local b = require(script.Parent.Module)",
+        "return require(201)"
+    ].join("\n")));
+
+    let found: Vec<(String, String, u64)> = backend.dom_find_asset_references(&dom)
+        .into_iter()
+        .map(|reference| (reference.path, reference.property, reference.asset_id))
+        .collect();
+
+    let expected = [
+        ("Model.Mesh", "MeshId", 101),
+        ("Model.Mesh", "TextureID", 102),
+        ("Model.Loader", "Source", 201),
+        ("Model.Loader", "Source", 202),
+        ("Model.Loader", "Source", 203),
+        ("Model.Loader", "Source", 204)
+    ];
+    assert_eq!(found.len(), expected.len(), "{:?}", found);
+    for (path, property, asset_id) in expected {
+        assert!(found.contains(&(path.to_string(), property.to_string(), asset_id)), "{}.{} -> {} not found in {:?}", path, property, asset_id, found);
+    }
+}
@@ -0,0 +1,61 @@
+use liquid_breakout_backend_v2::roblox::{AssetType, CatalogItemDetails, CreatorType, ItemDetails};
+use serde_json::json;
+
+fn item_details(asset_type_id: u64) -> serde_json::Value {
+    json!({
+        "AssetId": 1818,
+        "TargetId": 1818,
+        "ProductId": 2,
+        "AssetTypeId": asset_type_id,
+        "Name": "Classic Crossroads",
+        "Description": "",
+        "Creator": { "Id": 1, "HasVerifiedBadge": true, "CreatorType": "User", "CreatorTargetId": 1, "Name": "Roblox" },
+        "IsPublicDomain": true
+    })
+}
+
+#[test]
+fn unlisted_asset_type_ids_deserialize_as_unknown() {
+    let details: ItemDetails = serde_json::from_value(item_details(999)).unwrap();
+    assert_eq!(details.asset_type_id, Some(AssetType::Unknown(999)));
+
+    // And are written back unchanged
+    assert_eq!(serde_json::to_value(&details).unwrap()["AssetTypeId"], 999);
+}
+
+#[test]
+fn listed_asset_type_ids_deserialize_as_their_type() {
+    let details: ItemDetails = serde_json::from_value(item_details(10)).unwrap();
+    assert_eq!(details.asset_type_id, Some(AssetType::Model));
+}
+
+#[test]
+fn catalog_items_convert_to_details_without_the_fields_they_lack() {
+    let item: CatalogItemDetails = serde_json::from_value(json!({
+        "id": 1818,
+        "itemType": "Asset",
+        "assetType": 8,
+        "name": "Classic Crossroads",
+        "description": "",
+        "productId": 2,
+        "creatorHasVerifiedBadge": true,
+        "creatorType": "Group",
+        "creatorTargetId": 7,
+        "creatorName": "Roblox",
+        "price": 50,
+        "isOffSale": false,
+        "itemRestrictions": ["Limited"],
+        "unitsAvailableForConsumption": 3
+    })).unwrap();
+
+    let details = ItemDetails::from(item);
+    assert_eq!(details.id, 1818);
+    assert_eq!(details.asset_type_id, Some(AssetType::Hat));
+    assert_eq!(details.creator.creator_type, CreatorType::Group);
+    assert_eq!(details.creator.target_id, 7);
+    assert_eq!(details.price_in_robux, Some(50));
+    assert_eq!(details.is_for_sale, Some(true));
+    assert_eq!((details.is_limited, details.is_limited_unique, details.remaining), (Some(true), Some(false), Some(3)));
+    assert_eq!(details.is_public_domain, None);
+    assert!(details.created.is_none() && details.sales.is_none());
+}
@@ -0,0 +1,61 @@
+use liquid_breakout_backend_v2::Backend;
+use liquid_breakout_backend_v2::roblox::{SanitizeAction, SanitizePolicy};
+use rbx_dom_weak::{InstanceBuilder, WeakDom};
+
+fn backend() -> Backend {
+    Backend::new(String::new(), vec!["abcdefghijklmnopqrstuvwxyz".to_string()])
+}
+
+fn model_with_script(source: &str) -> WeakDom {
+    let mut dom = WeakDom::new(InstanceBuilder::new("DataModel"));
+    let model = dom.insert(dom.root_ref(), InstanceBuilder::new("Model").with_name("Model"));
+    dom.insert(model, InstanceBuilder::new("Script").with_name("Script").with_property("Source", source.to_string()));
+
+    dom
+}
+
+fn loadstring_policy() -> SanitizePolicy {
+    SanitizePolicy { banned_functions: vec!["loadstring".to_string()], ..SanitizePolicy::default() }
+}
+
+#[test]
+fn banned_globals_are_found_anywhere_in_scripts() {
+    let backend = backend();
+    let sources = [
+        "loadstring(s)()",
+        "local f = function() loadstring(s)() end",
+        "if x then else loadstring(s)() end",
+        "if x then elseif y then loadstring(s)() end",
+        "return loadstring(s)()",
+        "print(loadstring(s))",
+        "local ls = loadstring; ls(s)()",
+        "if loadstring then end",
+        "local t = { run = loadstring }",
+        "while true do task.spawn(function() local x = `{loadstring(s)()}` end) end"
+    ];
+
+    for source in sources {
+        let mut dom = model_with_script(source);
+        let changes = backend.dom_sanitize(&mut dom, &loadstring_policy());
+
+        assert_eq!(changes.len(), 1, "{} was not flagged", source);
+        assert_eq!(changes[0].action, SanitizeAction::RemovedScript);
+        assert_eq!(changes[0].path, "Model.Script");
+    }
+}
+
+#[test]
+fn scripts_without_banned_globals_are_kept() {
+    let backend = backend();
+
+    let mut dom = model_with_script("local module = {}\nmodule.loadstring = 'not the global'\nprint(module.loadstring)\nreturn module");
+    assert!(backend.dom_sanitize(&mut dom, &loadstring_policy()).is_empty());
+}
+
+#[test]
+fn global_references_are_reported_in_source_order() {
+    let backend = backend();
+
+    let ast = backend.luau_ast_from_string("local a = getfenv\nreturn getfenv(1), a").unwrap();
+    assert_eq!(backend.luau_find_global_references(&ast, "getfenv"), vec![(10, 17), (25, 32)]);
+}
@@ -0,0 +1,69 @@
+use liquid_breakout_backend_v2::Backend;
+use liquid_breakout_backend_v2::roblox::{ScriptChange, ScriptChangeKind};
+use rbx_dom_weak::{InstanceBuilder, WeakDom};
+
+fn backend() -> Backend {
+    Backend::new(String::new(), vec!["abcdefghijklmnopqrstuvwxyz".to_string()])
+}
+
+fn script(name: &str, source: &str) -> InstanceBuilder {
+    InstanceBuilder::new("Script").with_name(name).with_property("Source", source.to_string())
+}
+
+// Model { Script, Script, Folder { LocalScript } } next to a root-level ModuleScript
+fn model(first: &str, second: &str, module: &str, with_local_script: bool) -> WeakDom {
+    let mut dom = WeakDom::new(InstanceBuilder::new("DataModel"));
+    dom.insert(dom.root_ref(), InstanceBuilder::new("ModuleScript").with_name("Module").with_property("Source", module.to_string()));
+
+    let model = dom.insert(dom.root_ref(), InstanceBuilder::new("Model").with_name("Model"));
+    dom.insert(model, script("Script", first));
+    dom.insert(model, script("Script", second));
+    let folder = dom.insert(model, InstanceBuilder::new("Folder").with_name("Folder"));
+    if with_local_script {
+        dom.insert(folder, InstanceBuilder::new("LocalScript").with_name("Client").with_property("Source", "print('client')".to_string()));
+    }
+
+    dom
+}
+
+fn change(path: &str, kind: ScriptChangeKind, before: Option<&str>, after: Option<&str>) -> ScriptChange {
+    ScriptChange { path: path.to_string(), kind, before: before.map(str::to_string), after: after.map(str::to_string) }
+}
+
+#[test]
+fn scripts_are_keyed_by_their_own_unique_path() {
+    let backend = backend();
+
+    let scripts = backend.dom_find_scripts(&model("print(1)", "print(2)", "return {}", true));
+    assert_eq!(scripts.len(), 4);
+    assert_eq!(scripts["Module"], "return {}");
+    assert_eq!(scripts["Model.Script"], "print(1)");
+    assert_eq!(scripts["Model.Script#2"], "print(2)");
+    assert_eq!(scripts["Model.Folder.Client"], "print('client')");
+}
+
+#[test]
+fn changes_to_same_named_siblings_and_root_scripts_are_reported() {
+    let backend = backend();
+
+    let before = model("print(1)", "print(2)", "return {}", false);
+    let after = model("print(1)", "getfenv()", "return { evil = true }", true);
+
+    assert_eq!(backend.dom_diff_scripts(&before, &after), vec![
+        change("Model.Folder.Client", ScriptChangeKind::Added, None, Some("print('client')")),
+        change("Model.Script#2", ScriptChangeKind::Modified, Some("print(2)"), Some("getfenv()")),
+        change("Module", ScriptChangeKind::Modified, Some("return {}"), Some("return { evil = true }"))
+    ]);
+    assert_eq!(backend.dom_diff_scripts(&after, &before)[0], change("Model.Folder.Client", ScriptChangeKind::Removed, Some("print('client')"), None));
+    assert!(backend.dom_diff_scripts(&after, &after).is_empty());
+}
+
+#[test]
+fn scripts_without_source_are_empty() {
+    let backend = backend();
+
+    let mut dom = WeakDom::new(InstanceBuilder::new("DataModel"));
+    dom.insert(dom.root_ref(), InstanceBuilder::new("Script").with_name("Script"));
+
+    assert_eq!(backend.dom_find_scripts(&dom)["Script"], "");
+}
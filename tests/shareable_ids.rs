@@ -0,0 +1,179 @@
+use liquid_breakout_backend_v2::{Backend, IDConversionError, IDNormalization};
+use proptest::prelude::*;
+
+const ALPHABET: &str = "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ";
+
+fn backend(check_character: bool) -> Backend {
+    let mut backend = Backend::new(String::new(), vec![ALPHABET.to_string(), "0123456789".to_string()]);
+    backend.set_shareable_id_check_character(check_character);
+    backend
+}
+
+fn keyed_backend(secret_key: &str) -> Backend {
+    let mut backend = backend(false);
+    backend.set_shareable_id_key(Some(secret_key));
+    backend
+}
+
+fn conversion_error(error: Box<dyn std::error::Error>) -> IDConversionError {
+    error.downcast_ref::<IDConversionError>().expect("error should be an IDConversionError").clone()
+}
+
+#[test]
+fn ids_without_zero_digits_keep_their_old_form() {
+    let backend = backend(false);
+
+    // 2 * 52 + 3, the old converter wrote the base 52 digits least significant first as alphabet[digit - 1]
+    assert_eq!(backend.get_shareable_id("107".to_string()).unwrap(), "cb");
+    assert_eq!(backend.get_number_id("cb".to_string()).unwrap(), 107);
+}
+
+#[test]
+fn legacy_ids_decode_and_round_trip_through_their_new_form() {
+    // Made by the old two-alphabet converter
+    let known_ids = [
+        ("0123456789", "cb", 107),
+        ("0123456789", "ICNh", 1234567),
+        ("0123456789", "EkEIWBEi", 9876543210123),
+        ("9876543210", "hq", 107)
+    ];
+
+    for (numbers, legacy_id, id) in known_ids {
+        let mut backend = Backend::new(String::new(), vec![ALPHABET.to_string(), numbers.to_string()]);
+        backend.set_shareable_id_check_character(true);
+
+        assert_eq!(backend.get_legacy_number_id(legacy_id.to_string()).unwrap(), id, "{}", legacy_id);
+        let shareable_id = backend.get_shareable_id(id.to_string()).unwrap();
+        assert_eq!(backend.get_number_id(shareable_id).unwrap(), id);
+    }
+
+    // Shuffled digits are why the old form needs its own decoder
+    let backend = Backend::new(String::new(), vec![ALPHABET.to_string(), "9876543210".to_string()]);
+    assert_ne!(backend.get_number_id("hq".to_string()).unwrap(), 107);
+}
+
+#[test]
+fn legacy_ids_need_the_numbers_alphabet() {
+    let backend = Backend::new(String::new(), vec![ALPHABET.to_string()]);
+    assert!(backend.get_legacy_number_id("cb".to_string()).is_err());
+}
+
+#[test]
+fn invalid_characters_are_reported_with_their_position() {
+    let backend = backend(false);
+
+    let error = conversion_error(backend.get_number_id("ab-c".to_string()).unwrap_err());
+    assert_eq!(error, IDConversionError::InvalidCharacter { character: '-', position: 3 });
+}
+
+#[test]
+fn overflowing_ids_are_rejected() {
+    let backend = backend(false);
+
+    let error = conversion_error(backend.get_number_id("Z".repeat(20)).unwrap_err());
+    assert_eq!(error, IDConversionError::Overflow);
+}
+
+#[test]
+fn shareable_ids_round_trip() {
+    let backends = [backend(false), backend(true)];
+
+    proptest!(|(id in 1..=u64::MAX, check_character in any::<bool>())| {
+        let backend = &backends[check_character as usize];
+
+        let shareable_id = backend.get_shareable_id(id.to_string()).unwrap();
+        prop_assert_eq!(backend.get_number_id(shareable_id).unwrap(), id);
+    });
+}
+
+#[test]
+fn mistyped_characters_are_caught_by_the_check_character() {
+    let backend = backend(true);
+
+    proptest!(|(id in 1..=u64::MAX, position in any::<prop::sample::Index>(), replacement in any::<prop::sample::Index>())| {
+        let mut characters: Vec<char> = backend.get_shareable_id(id.to_string()).unwrap().chars().collect();
+        let position = position.index(characters.len());
+        let alphabet: Vec<char> = ALPHABET.chars().filter(|c| *c != characters[position]).collect();
+        characters[position] = alphabet[replacement.index(alphabet.len())];
+
+        prop_assert!(backend.get_number_id(characters.into_iter().collect()).is_err());
+    });
+}
+
+#[test]
+fn keyed_ids_round_trip() {
+    let backend = keyed_backend("correct horse battery staple");
+
+    proptest!(|(id in 1..=u64::MAX)| {
+        let shareable_id = backend.get_shareable_id(id.to_string()).unwrap();
+        prop_assert_eq!(backend.get_number_id(shareable_id).unwrap(), id);
+    });
+
+    // The extremes are where cycle walking around 0 would go wrong
+    for id in [1, 2, u64::MAX - 1, u64::MAX] {
+        let shareable_id = backend.get_shareable_id(id.to_string()).unwrap();
+        assert_eq!(backend.get_number_id(shareable_id).unwrap(), id);
+    }
+}
+
+#[test]
+fn keyed_ids_do_not_look_sequential() {
+    let plain = backend(false);
+    let keyed = keyed_backend("correct horse battery staple");
+    let other_key = keyed_backend("another key");
+
+    let first = keyed.get_shareable_id("1000".to_string()).unwrap();
+    let second = keyed.get_shareable_id("1001".to_string()).unwrap();
+
+    assert_ne!(first, plain.get_shareable_id("1000".to_string()).unwrap());
+    assert_ne!(first, other_key.get_shareable_id("1000".to_string()).unwrap());
+    assert!(first.chars().zip(second.chars()).filter(|(a, b)| a != b).count() > 1);
+}
+
+fn normalized_backend() -> Backend {
+    let mut backend = Backend::new(String::new(), vec!["abcdefghijkmnopqrstuvwxyz23456789".to_string()]);
+    backend.set_shareable_id_check_character(true);
+    backend.set_shareable_id_normalization(IDNormalization {
+        case_insensitive: true,
+        confusables: vec![('1', 'i'), ('l', 'i'), ('0', 'o')],
+        ignored_separators: vec![' '],
+        group_size: Some(4),
+        ..IDNormalization::default()
+    }).unwrap();
+    backend
+}
+
+#[test]
+fn normalized_ids_round_trip_when_mangled() {
+    let backend = normalized_backend();
+
+    proptest!(|(id in 1..=u64::MAX)| {
+        let shareable_id = backend.get_shareable_id(id.to_string()).unwrap();
+        prop_assert!(shareable_id.split('-').all(|group| group.chars().count() <= 4));
+        prop_assert_eq!(backend.get_number_id(shareable_id.clone()).unwrap(), id);
+
+        let mangled: String = shareable_id.replace('-', " ").to_uppercase();
+        prop_assert_eq!(backend.get_number_id(mangled).unwrap(), id);
+        let ungrouped: String = shareable_id.replace('-', "");
+        prop_assert_eq!(backend.get_number_id(ungrouped).unwrap(), id);
+    });
+}
+
+#[test]
+fn confusable_characters_decode_as_what_was_meant() {
+    let backend = normalized_backend();
+
+    let shareable_id = backend.get_shareable_id("14".to_string()).unwrap();
+    let confused = shareable_id.replace('o', "0");
+    assert_ne!(confused, shareable_id);
+    assert_eq!(backend.get_number_id(confused).unwrap(), 14);
+}
+
+#[test]
+fn ambiguous_normalizations_are_rejected() {
+    let mut backend = backend(false);
+
+    // The alphabet has both cases of every letter
+    assert!(backend.set_shareable_id_normalization(IDNormalization { case_insensitive: true, ..IDNormalization::default() }).is_err());
+    assert!(backend.set_shareable_id_normalization(IDNormalization { ignored_separators: vec!['a'], ..IDNormalization::default() }).is_err());
+}
@@ -0,0 +1,104 @@
+use liquid_breakout_backend_v2::{Backend, IDConversionError};
+use liquid_breakout_backend_v2::shareable_ids::{MapId, UserId};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+fn backend() -> Backend {
+    Backend::new(String::new(), vec!["abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ".to_string()])
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct MapEntry {
+    #[serde(rename = "mapId")]
+    map_id: MapId,
+    #[serde(rename = "creatorId", serialize_with = "UserId::serialize_number", deserialize_with = "UserId::deserialize_number")]
+    creator_id: UserId
+}
+
+#[test]
+fn typed_ids_round_trip_through_display_and_from_str() {
+    let backend = backend();
+
+    let map_id = MapId(107);
+    backend.with_shareable_ids(|| {
+        assert_eq!(map_id.to_string(), format!("map_{}", backend.get_shareable_id("107".to_string()).unwrap()));
+        assert_eq!(map_id.to_string().parse::<MapId>().unwrap(), map_id);
+    });
+    assert_eq!(map_id.to_shareable(&backend).unwrap(), "map_cb");
+    assert_eq!(MapId::from_shareable("MAP_cb", &backend).unwrap(), map_id);
+}
+
+#[test]
+fn typed_ids_reject_other_kinds() {
+    let backend = backend();
+
+    let user_id = UserId(107).to_shareable(&backend).unwrap();
+    assert_eq!(MapId::from_shareable(&user_id, &backend).unwrap_err(), IDConversionError::WrongPrefix { expected: MapId::PREFIX });
+    assert_eq!(backend.with_shareable_ids(|| user_id.parse::<MapId>()).unwrap_err(), IDConversionError::WrongPrefix { expected: MapId::PREFIX });
+}
+
+#[test]
+fn typed_ids_serialize_as_shareable_ids_and_stored_fields_as_numbers() {
+    let backend = backend();
+
+    let entry = MapEntry { map_id: MapId(107), creator_id: UserId(5) };
+    let json = backend.with_shareable_ids(|| serde_json::to_value(&entry).unwrap());
+    assert_eq!(json["mapId"], "map_cb");
+    assert_eq!(json["creatorId"], 5);
+    assert_eq!(backend.with_shareable_ids(|| serde_json::from_value::<MapEntry>(json).unwrap()), entry);
+}
+
+#[test]
+fn typed_ids_do_not_fall_back_to_numbers() {
+    let backend = backend();
+
+    // Outside of a scope there's nothing to convert with
+    assert!(serde_json::to_value(MapId(107)).is_err());
+    assert!(serde_json::from_value::<MapId>(json!("map_cb")).is_err());
+    assert_eq!("map_cb".parse::<MapId>().unwrap_err(), IDConversionError::NotConfigured);
+    assert_eq!(MapId(107).to_string(), "map_?");
+
+    // And numbers aren't shareable IDs, even inside of one
+    backend.with_shareable_ids(|| {
+        assert!(serde_json::from_value::<MapId>(json!(107)).is_err());
+        assert_eq!("107".parse::<MapId>().unwrap_err(), IDConversionError::WrongPrefix { expected: MapId::PREFIX });
+        assert!(serde_json::to_value(MapId(0)).is_err());
+        assert_eq!(MapId(0).to_string(), "map_?");
+    });
+}
+
+#[test]
+fn stored_ids_must_be_non_negative_numbers() {
+    let entry = |creator_id: serde_json::Value| serde_json::from_value::<MapEntry>(json!({ "mapId": "map_cb", "creatorId": creator_id }));
+    let backend = backend();
+
+    backend.with_shareable_ids(|| {
+        assert_eq!(entry(json!(5)).unwrap().creator_id, UserId(5));
+        assert!(entry(json!(-5)).is_err());
+        assert!(entry(json!("user_cb")).is_err());
+        // Mongo has no unsigned integers to store it in
+        assert!(serde_json::to_value(MapEntry { map_id: MapId(107), creator_id: UserId(u64::MAX) }).is_err());
+    });
+}
+
+#[test]
+fn backends_do_not_share_converters() {
+    let plain = backend();
+    let mut keyed = backend();
+    keyed.set_shareable_id_key(Some("correct horse battery staple"));
+
+    let map_id = MapId(107);
+    let plain_id = plain.with_shareable_ids(|| map_id.to_string());
+    let keyed_id = keyed.with_shareable_ids(|| map_id.to_string());
+
+    assert_eq!(plain_id, "map_cb");
+    assert_ne!(keyed_id, plain_id);
+    assert_eq!(keyed.with_shareable_ids(|| keyed_id.parse::<MapId>()).unwrap(), map_id);
+    assert_eq!(plain.with_shareable_ids(|| plain_id.parse::<MapId>()).unwrap(), map_id);
+
+    // Scopes nest, the enclosing backend is back once the inner scope ends
+    plain.with_shareable_ids(|| {
+        assert_eq!(keyed.with_shareable_ids(|| map_id.to_string()), keyed_id);
+        assert_eq!(map_id.to_string(), plain_id);
+    });
+}